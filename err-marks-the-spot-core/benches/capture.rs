@@ -0,0 +1,26 @@
+//! The cost of creating an `ErrorCtx` with each `CapturePolicy`.
+//! Run with `cargo bench -p err-marks-the-spot-core`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use err_marks_the_spot_core::{CapturePolicy, ErrorCtx};
+use std::hint::black_box;
+
+fn capture_policies(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ErrorCtx::with_capture_policy");
+    let policies = [
+        ("location_only", CapturePolicy::LocationOnly),
+        ("force", CapturePolicy::Force),
+        ("cached", CapturePolicy::Cached),
+    ];
+    for (name, policy) in policies {
+        group.bench_function(name, |b| {
+            // Every iteration creates an ErrorCtx at the same location,
+            // like a hot error path in a loop does:
+            b.iter(|| black_box(ErrorCtx::with_capture_policy(policy)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, capture_policies);
+criterion_main!(benches);
@@ -0,0 +1,200 @@
+//! Control over whether an [`ErrorCtx`](crate::ErrorCtx) captures a backtrace.
+
+use crate::CaptureStatus;
+use crate::frame::{self, BacktraceFrame};
+use crate::location::CtxLocation;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+
+/// The name of the environment variable that sets the initial process-wide
+/// [`CapturePolicy`].
+/// Its value is one of `off`, `location`, `env`, `force` or `cached`.
+pub const CAPTURE_POLICY_ENV_VAR: &str = "ERR_MARKS_THE_SPOT_BACKTRACE";
+
+/// The policy that decides whether an `ErrorCtx` captures a backtrace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CapturePolicy {
+    /// Don't capture backtraces. When this is the process-wide policy, it
+    /// overrides the policy of every type.
+    ///
+    /// This only affects capturing: the location where the error was created
+    /// is still recorded and rendered. To render errors without their
+    /// `ErrorCtx`, use [`RenderStyle::Message`](crate::RenderStyle::Message).
+    Off,
+    /// Only record the location where the error was created.
+    LocationOnly,
+    /// Capture a backtrace if the `RUST_LIB_BACKTRACE` or `RUST_BACKTRACE`
+    /// environment variables enable it, like `Backtrace::capture()` does.
+    #[default]
+    Env,
+    /// Always capture a backtrace, like `Backtrace::force_capture()` does.
+    Force,
+    /// Capture and resolve a backtrace only once per location, and share it
+    /// with every later `ErrorCtx` created at that location. This makes
+    /// creating errors on hot paths cheap, at the cost of showing the call
+    /// path of the first error created at each location.
+    ///
+    /// At most [`BACKTRACE_CACHE_CAPACITY`] locations are cached at a time.
+    Cached,
+}
+
+impl CapturePolicy {
+    fn from_env_var_value(value: &str) -> Option<Self> {
+        match value {
+            "off" => Some(Self::Off),
+            "location" => Some(Self::LocationOnly),
+            "env" => Some(Self::Env),
+            "force" => Some(Self::Force),
+            "cached" => Some(Self::Cached),
+            _ => None,
+        }
+    }
+}
+
+/// The process-wide policy, or `UNSET` if it has been neither set explicitly
+/// nor read from the environment yet.
+static CAPTURE_POLICY: AtomicU8 = AtomicU8::new(UNSET);
+const UNSET: u8 = u8::MAX;
+
+/// Set the process-wide [`CapturePolicy`].
+/// This overrides the [`CAPTURE_POLICY_ENV_VAR`] environment variable.
+pub fn set_capture_policy(policy: CapturePolicy) {
+    CAPTURE_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// Get the process-wide [`CapturePolicy`]. Unless it is set explicitly, it is
+/// read once from the [`CAPTURE_POLICY_ENV_VAR`] environment variable, and it
+/// defaults to [`CapturePolicy::Env`].
+pub fn capture_policy() -> CapturePolicy {
+    match CAPTURE_POLICY.load(Ordering::Relaxed) {
+        0 => CapturePolicy::Off,
+        1 => CapturePolicy::LocationOnly,
+        2 => CapturePolicy::Env,
+        3 => CapturePolicy::Force,
+        4 => CapturePolicy::Cached,
+        _ => {
+            let policy = std::env::var(CAPTURE_POLICY_ENV_VAR)
+                .ok()
+                .and_then(|value| CapturePolicy::from_env_var_value(&value))
+                .unwrap_or_default();
+            // Don't override a policy that was set concurrently:
+            let _ = CAPTURE_POLICY.compare_exchange(
+                UNSET,
+                policy as u8,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+            policy
+        }
+    }
+}
+
+/// The backtrace of an `ErrorCtx`, which is either owned by it or shared with
+/// the other `ErrorCtx`s created at the same location. A context that was
+/// restored from an `OwnedErrorCtx` has only the resolved frames.
+pub(crate) enum CtxBacktrace {
+    Owned(Backtrace),
+    Shared(Arc<SharedBacktrace>),
+    Restored(CaptureStatus, Vec<BacktraceFrame>),
+}
+
+/// A backtrace that is shared by all `ErrorCtx`s created at a location,
+/// along with its resolved frames.
+pub(crate) struct SharedBacktrace {
+    backtrace: Backtrace,
+    frames: Vec<BacktraceFrame>,
+}
+
+impl CtxBacktrace {
+    /// Capture a backtrace at `location` according to `policy`.
+    pub(crate) fn capture(
+        policy: CapturePolicy,
+        location: &CtxLocation,
+    ) -> Self {
+        match policy {
+            CapturePolicy::Off | CapturePolicy::LocationOnly => {
+                Self::Owned(Backtrace::disabled())
+            }
+            CapturePolicy::Env => Self::Owned(Backtrace::capture()),
+            CapturePolicy::Force => Self::Owned(Backtrace::force_capture()),
+            CapturePolicy::Cached => Self::Shared(cached_backtrace(location)),
+        }
+    }
+
+    pub(crate) fn backtrace(&self) -> &Backtrace {
+        static DISABLED: Backtrace = Backtrace::disabled();
+        match self {
+            Self::Owned(backtrace) => backtrace,
+            Self::Shared(shared) => &shared.backtrace,
+            Self::Restored(..) => &DISABLED,
+        }
+    }
+
+    pub(crate) fn status(&self) -> BacktraceStatus {
+        match self {
+            Self::Restored(status, _) => BacktraceStatus::from(*status),
+            _ => self.backtrace().status(),
+        }
+    }
+
+    pub(crate) fn frames(&self) -> Vec<BacktraceFrame> {
+        match self {
+            Self::Owned(backtrace) => frame::resolve_frames(backtrace),
+            Self::Shared(shared) => shared.frames.clone(),
+            Self::Restored(_, frames) => frames.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for CtxBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Restored(status, frames) => f
+                .debug_struct("Restored")
+                .field("status", status)
+                .field("frames", frames)
+                .finish(),
+            _ => fmt::Debug::fmt(self.backtrace(), f),
+        }
+    }
+}
+
+/// The maximum number of locations for which [`CapturePolicy::Cached`]
+/// caches a backtrace. When the cache is full, an arbitrary location is
+/// evicted to make room for a new one.
+pub const BACKTRACE_CACHE_CAPACITY: usize = 1024;
+
+type BacktraceCache = HashMap<CtxLocation, Arc<SharedBacktrace>>;
+
+static BACKTRACE_CACHE: LazyLock<RwLock<BacktraceCache>> =
+    LazyLock::new(Default::default);
+
+/// Get the cached backtrace for `location`,
+/// capturing and resolving it if it isn't cached yet.
+fn cached_backtrace(location: &CtxLocation) -> Arc<SharedBacktrace> {
+    let cache = BACKTRACE_CACHE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(shared) = cache.get(location) {
+        return Arc::clone(shared);
+    }
+    drop(cache);
+
+    // Capture and resolve without holding the lock, since that is slow:
+    let backtrace = Backtrace::force_capture();
+    let frames = frame::resolve_frames(&backtrace);
+    let shared = Arc::new(SharedBacktrace { backtrace, frames });
+    let mut cache = BACKTRACE_CACHE
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    if cache.len() >= BACKTRACE_CACHE_CAPACITY && !cache.contains_key(location)
+    {
+        let evicted = cache.keys().next().unwrap().clone();
+        cache.remove(&evicted);
+    }
+    // If another thread cached a backtrace in the meantime, use that one:
+    Arc::clone(cache.entry(location.clone()).or_insert(shared))
+}
@@ -0,0 +1,116 @@
+//! Control over whether [`ErrorCtx`](crate::ErrorCtx) output is colored.
+
+use crate::location::AnyLocation;
+use crate::normalize;
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The process-wide policy that decides whether output is colored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorPolicy {
+    /// Color the output if `stderr` is a terminal. The `NO_COLOR` and
+    /// `CLICOLOR_FORCE` environment variables override this, in that order.
+    #[default]
+    Auto,
+    /// Always color the output.
+    Always,
+    /// Never color the output.
+    Never,
+}
+
+static COLOR_POLICY: AtomicU8 = AtomicU8::new(ColorPolicy::Auto as u8);
+
+/// Set the process-wide [`ColorPolicy`].
+pub fn set_color_policy(policy: ColorPolicy) {
+    COLOR_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// Get the process-wide [`ColorPolicy`].
+pub fn color_policy() -> ColorPolicy {
+    match COLOR_POLICY.load(Ordering::Relaxed) {
+        1 => ColorPolicy::Always,
+        2 => ColorPolicy::Never,
+        _ => ColorPolicy::Auto,
+    }
+}
+
+/// Whether output is colored, according to the current [`ColorPolicy`].
+/// This is always `false` if the `color` feature is disabled.
+pub fn colors_enabled() -> bool {
+    if cfg!(not(feature = "color")) {
+        return false;
+    }
+    match color_policy() {
+        ColorPolicy::Always => true,
+        ColorPolicy::Never => false,
+        ColorPolicy::Auto => {
+            // See https://no-color.org and https://bixense.com/clicolors
+            if env_var("NO_COLOR").is_some() {
+                return false;
+            }
+            if let Some(force) = env_var("CLICOLOR_FORCE") {
+                return force != "0";
+            }
+            std::io::stderr().is_terminal()
+        }
+    }
+}
+
+/// Get the value of an environment variable, treating empty values as unset.
+fn env_var(name: &str) -> Option<std::ffi::OsString> {
+    std::env::var_os(name).filter(|value| !value.is_empty())
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Cyan,
+}
+
+/// Paints text in a [`Color`], or leaves it as-is when colors are disabled.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    /// A palette that follows the current [`ColorPolicy`], unless output is
+    /// normalized (see [`Normalized`](crate::Normalized)).
+    pub(crate) fn current() -> Self {
+        Self {
+            enabled: colors_enabled() && !normalize::normalizing(),
+        }
+    }
+
+    /// Paint a location as `file:line:column`.
+    pub(crate) fn paint_location(&self, location: &impl AnyLocation) -> String {
+        let file = normalize::normalize_path(location.file());
+        let file = self.paint(Color::Blue, file);
+        let line = self.paint(Color::Green, location.line());
+        let column = self.paint(Color::Yellow, location.column());
+        format!("{file}:{line}:{column}")
+    }
+
+    #[cfg_attr(not(feature = "color"), allow(unused_variables))]
+    pub(crate) fn paint(&self, color: Color, text: impl Display) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+        #[cfg(feature = "color")]
+        let text = {
+            let color = match color {
+                Color::Red => ansi_term::Color::Red,
+                Color::Green => ansi_term::Color::Green,
+                Color::Yellow => ansi_term::Color::Yellow,
+                Color::Blue => ansi_term::Color::Blue,
+                Color::Cyan => ansi_term::Color::Cyan,
+            };
+            color.paint(text.to_string())
+        };
+        text.to_string()
+    }
+}
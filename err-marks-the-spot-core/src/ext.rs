@@ -0,0 +1,122 @@
+//! Extension methods for `Result`s and `Option`s.
+
+use crate::{HasErrorCtx, Input, Located};
+use std::ops::Range;
+
+/// Extension methods for `Result`s.
+pub trait ResultExt<T, E> {
+    /// Record that the error (if any) was propagated through the caller's
+    /// location, without capturing a new backtrace. Calling this at each
+    /// `?` builds a trail from the location where the error was created to
+    /// the outermost location it was returned from:
+    /// ```ignore
+    /// let config = parse_config(path).trace()?;
+    /// ```
+    #[track_caller]
+    fn trace(self) -> Self
+    where
+        E: HasErrorCtx;
+
+    /// Label the byte `range` of `input` in the `ErrorCtx` of the error
+    /// (if any). See [`ErrorCtx::add_span`](crate::ErrorCtx::add_span):
+    /// ```ignore
+    /// let port = parse_port(value).with_span(&input, range, "invalid port")?;
+    /// ```
+    fn with_span(
+        self,
+        input: &Input,
+        range: Range<usize>,
+        label: impl Into<String>,
+    ) -> Self
+    where
+        E: HasErrorCtx;
+
+    /// Wrap the error (if any) in a [`Located`],
+    /// recording the caller's location:
+    /// ```ignore
+    /// let text = std::fs::read_to_string(path).located()?;
+    /// ```
+    #[track_caller]
+    fn located(self) -> Result<T, Located<E>>;
+}
+
+impl<T, E> ResultExt<T, E> for Result<T, E> {
+    #[track_caller]
+    fn trace(mut self) -> Self
+    where
+        E: HasErrorCtx,
+    {
+        if let Err(error) = &mut self {
+            if let Some(ctx) = error.ctx_mut() {
+                ctx.trace();
+            }
+        }
+        self
+    }
+
+    fn with_span(
+        mut self,
+        input: &Input,
+        range: Range<usize>,
+        label: impl Into<String>,
+    ) -> Self
+    where
+        E: HasErrorCtx,
+    {
+        if let Err(error) = &mut self {
+            if let Some(ctx) = error.ctx_mut() {
+                ctx.add_span(input, range, label);
+            }
+        }
+        self
+    }
+
+    #[track_caller]
+    fn located(self) -> Result<T, Located<E>> {
+        // NOTE: `map_err()` would record a location inside of `map_err()`,
+        //       because closures cannot be `#[track_caller]`.
+        match self {
+            Ok(value) => Ok(value),
+            Err(error) => Err(Located::new(error)),
+        }
+    }
+}
+
+/// Extension methods for `Option`s.
+pub trait OptionExt<T> {
+    /// Like `Option::ok_or()`, but wraps `error` in a [`Located`],
+    /// recording the caller's location:
+    /// ```ignore
+    /// let user = users.get(&id).ok_or_located(UnknownUser(id))?;
+    /// ```
+    #[track_caller]
+    fn ok_or_located<E>(self, error: E) -> Result<T, Located<E>>;
+
+    /// Like `Option::ok_or_else()`, but wraps the error in a [`Located`],
+    /// recording the caller's location (rather than one inside `f`).
+    #[track_caller]
+    fn ok_or_else_located<E, F>(self, f: F) -> Result<T, Located<E>>
+    where
+        F: FnOnce() -> E;
+}
+
+impl<T> OptionExt<T> for Option<T> {
+    #[track_caller]
+    fn ok_or_located<E>(self, error: E) -> Result<T, Located<E>> {
+        match self {
+            Some(value) => Ok(value),
+            None => Err(Located::new(error)),
+        }
+    }
+
+    #[track_caller]
+    fn ok_or_else_located<E, F>(self, f: F) -> Result<T, Located<E>>
+    where
+        F: FnOnce() -> E,
+    {
+        match self {
+            Some(value) => Ok(value),
+            None => Err(Located::new(f())),
+        }
+    }
+}
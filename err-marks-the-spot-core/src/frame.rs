@@ -0,0 +1,397 @@
+//! Structured access to the frames of a captured [`Backtrace`].
+
+use crate::color::{Color, Palette};
+use crate::location::AnyLocation;
+use crate::snippet::{self, SourceSnippets};
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt::{self, Formatter};
+use std::sync::{PoisonError, RwLock};
+
+/// A single resolved frame of a captured backtrace.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BacktraceFrame {
+    function: String,
+    file: Option<String>,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+impl BacktraceFrame {
+    /// The (demangled) name of the function, e.g. `my_crate::parse`.
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    /// The source file of the function, if known.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// The line in the source file, if known.
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    /// The column in the source file, if known.
+    pub fn column(&self) -> Option<u32> {
+        self.column
+    }
+
+    /// The name of the crate that the function belongs to, e.g. `my_crate`
+    /// for `my_crate::parse` or `<my_crate::Parser as Default>::default`.
+    /// For trait impls on types without a path, such as function pointers,
+    /// this is the crate of the trait instead.
+    /// This is `None` for functions without a path, e.g. `main`.
+    pub fn crate_name(&self) -> Option<&str> {
+        let function = &self.function;
+        path_crate_name(function).or_else(|| {
+            let (_, trait_path) = function.split_once(" as ")?;
+            path_crate_name(trait_path)
+        })
+    }
+
+    /// Whether the frame belongs to the standard library, the test harness,
+    /// the runtime or `err_marks_the_spot` itself rather than to user code.
+    pub fn is_runtime(&self) -> bool {
+        const RUNTIME_CRATES: &[&str] =
+            &["std", "core", "alloc", "test", "err_marks_the_spot_core"];
+        const RUNTIME_FUNCTIONS: &[&str] = &[
+            "_start",
+            "__libc_start_main",
+            "__libc_start_call_main",
+            "start_thread",
+            "clone",
+            "clone3",
+            "__clone",
+            "__clone3",
+            "BaseThreadInitThunk",
+            "RtlUserThreadStart",
+            "<unknown>",
+        ];
+        self.crate_name()
+            .is_some_and(|crate_name| RUNTIME_CRATES.contains(&crate_name))
+            || RUNTIME_FUNCTIONS.contains(&&*self.function)
+            || self.function.contains("__rust_begin_short_backtrace")
+            || self.function.contains("__rust_end_short_backtrace")
+    }
+
+    /// Whether the frame is the one at `location`.
+    fn is_at(&self, location: &impl AnyLocation) -> bool {
+        self.line == Some(location.line())
+            && self.column == Some(location.column())
+            && self.file.as_deref().map(file_name)
+                == Some(file_name(location.file()))
+    }
+}
+
+/// The first segment of a path such as `my_crate::parse`, if it has more
+/// than one segment. Leading `<` and `&` characters are skipped.
+fn path_crate_name(path: &str) -> Option<&str> {
+    let path = path.trim_start_matches(['<', '&']);
+    let end = path.find(|c: char| !c.is_alphanumeric() && c != '_')?;
+    let (crate_name, rest) = path.split_at(end);
+    (!crate_name.is_empty() && rest.starts_with("::")).then_some(crate_name)
+}
+
+/// The last component of a `path`.
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// Resolve the frames of a `backtrace`.
+/// A backtrace that wasn't captured has no frames.
+///
+/// NOTE: `std` doesn't offer a stable API to walk the frames of a backtrace,
+///       so this parses its `Display` output on a best-effort basis. That
+///       format is explicitly unstable and may change in any Rust release.
+///       Lines that don't match it are skipped, so a changed format yields
+///       fewer (or no) frames rather than an error. It currently looks like:
+/// ```text
+///    0: my_crate::parse
+///              at ./src/lib.rs:16:24
+///    1: main
+/// ```
+pub(crate) fn resolve_frames(backtrace: &Backtrace) -> Vec<BacktraceFrame> {
+    if backtrace.status() != BacktraceStatus::Captured {
+        return vec![];
+    }
+    let mut frames: Vec<BacktraceFrame> = vec![];
+    for line in backtrace.to_string().lines() {
+        let line = line.trim();
+        if let Some(location) = line.strip_prefix("at ") {
+            let Some(frame) = frames.last_mut() else {
+                continue;
+            };
+            let (file, line, column) = parse_file_location(location);
+            frame.file = Some(file.to_string());
+            frame.line = line;
+            frame.column = column;
+        } else if let Some((index, function)) = line.split_once(": ") {
+            if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+                continue;
+            }
+            frames.push(BacktraceFrame {
+                function: function.to_string(),
+                file: None,
+                line: None,
+                column: None,
+            });
+        }
+    }
+    frames
+}
+
+/// Split e.g. `./src/lib.rs:16:24` into its file, line and column.
+/// The file itself may contain colons e.g. on Windows, so split from the end.
+fn parse_file_location(location: &str) -> (&str, Option<u32>, Option<u32>) {
+    let mut parts = location.rsplitn(3, ':');
+    let last = parts.next();
+    let middle = parts.next();
+    let rest = parts.next();
+    match (rest, middle, last) {
+        (Some(file), Some(line), Some(column)) => {
+            match (line.parse().ok(), column.parse().ok()) {
+                (Some(line), Some(column)) => (file, Some(line), Some(column)),
+                _ => (location, None, None),
+            }
+        }
+        _ => (location, None, None),
+    }
+}
+
+/// Which frames of a backtrace are shown when an `ErrorCtx` is rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FrameFilter {
+    /// Hide the frames of the standard library, the test harness and the
+    /// runtime (see [`BacktraceFrame::is_runtime`]). Each run of hidden frames
+    /// is collapsed into a single `... N frames hidden` line.
+    pub hide_runtime_frames: bool,
+    /// The maximum number of frames that are shown, if any.
+    /// Hidden frames don't count towards this maximum.
+    pub max_frames: Option<usize>,
+}
+
+impl FrameFilter {
+    /// Hide runtime frames, and show all other frames.
+    pub const fn new() -> Self {
+        Self {
+            hide_runtime_frames: true,
+            max_frames: None,
+        }
+    }
+
+    /// Show all frames, like the `Display` impl of `Backtrace` does.
+    pub const fn show_all() -> Self {
+        Self {
+            hide_runtime_frames: false,
+            max_frames: None,
+        }
+    }
+}
+
+impl Default for FrameFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static FRAME_FILTER: RwLock<FrameFilter> = RwLock::new(FrameFilter::new());
+
+/// Set the process-wide [`FrameFilter`].
+pub fn set_frame_filter(filter: FrameFilter) {
+    *FRAME_FILTER.write().unwrap_or_else(PoisonError::into_inner) = filter;
+}
+
+/// Get the process-wide [`FrameFilter`].
+pub fn frame_filter() -> FrameFilter {
+    *FRAME_FILTER.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Write the `frames` of a backtrace captured at `location`, filtered by
+/// `filter`. The frames of the crate that contains `location` are highlighted.
+/// The innermost frames that aren't runtime frames are shown with a snippet,
+/// if `snippets` are enabled.
+pub(crate) fn fmt_frames(
+    frames: &[BacktraceFrame],
+    location: &impl AnyLocation,
+    filter: FrameFilter,
+    snippets: Option<SourceSnippets>,
+    palette: &Palette,
+    f: &mut Formatter<'_>,
+) -> fmt::Result {
+    let origin_crate = frames
+        .iter()
+        .find(|frame| frame.is_at(location))
+        .and_then(BacktraceFrame::crate_name);
+    let mut hidden = 0;
+    let mut shown = 0;
+    let mut snipped = 0;
+    for (idx, frame) in frames.iter().enumerate() {
+        if filter.hide_runtime_frames && frame.is_runtime() {
+            hidden += 1;
+            continue;
+        }
+        fmt_hidden(hidden, f)?;
+        hidden = 0;
+        if filter
+            .max_frames
+            .is_some_and(|max_frames| shown >= max_frames)
+        {
+            let omitted = frames[idx..]
+                .iter()
+                .filter(|frame| {
+                    !(filter.hide_runtime_frames && frame.is_runtime())
+                })
+                .count();
+            return writeln!(f, "      ... {omitted} more frames omitted");
+        }
+        shown += 1;
+        let function = frame.function();
+        if origin_crate.is_some() && frame.crate_name() == origin_crate {
+            let function = palette.paint(Color::Cyan, function);
+            writeln!(f, "{idx:>4}: {function}")?;
+        } else {
+            writeln!(f, "{idx:>4}: {function}")?;
+        }
+        if let Some(file) = frame.file() {
+            write!(f, "             at {file}")?;
+            let (Some(line), Some(column)) = (frame.line(), frame.column())
+            else {
+                writeln!(f)?;
+                continue;
+            };
+            writeln!(f, ":{line}:{column}")?;
+            let Some(snippets) = snippets else { continue };
+            if snipped < snippets.frames && !frame.is_runtime() {
+                snipped += 1;
+                let indent = "             ";
+                snippet::fmt_snippet(
+                    file, line, column, snippets, indent, palette, f,
+                )?;
+            }
+        }
+    }
+    fmt_hidden(hidden, f)
+}
+
+fn fmt_hidden(hidden: usize, f: &mut Formatter<'_>) -> fmt::Result {
+    match hidden {
+        0 => Ok(()),
+        1 => writeln!(f, "      ... 1 frame hidden"),
+        _ => writeln!(f, "      ... {hidden} frames hidden"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_file_locations() {
+        assert_eq!(
+            parse_file_location("./src/lib.rs:16:24"),
+            ("./src/lib.rs", Some(16), Some(24)),
+        );
+        assert_eq!(
+            parse_file_location(r"C:\src\lib.rs:16:24"),
+            (r"C:\src\lib.rs", Some(16), Some(24)),
+        );
+        assert_eq!(
+            parse_file_location("./src/lib.rs:16"),
+            ("./src/lib.rs:16", None, None),
+        );
+        assert_eq!(
+            parse_file_location("./src/lib.rs:x:24"),
+            ("./src/lib.rs:x:24", None, None),
+        );
+        assert_eq!(parse_file_location("<unknown>"), ("<unknown>", None, None));
+    }
+
+    #[test]
+    fn path_crate_names() {
+        assert_eq!(path_crate_name("my_crate::parse"), Some("my_crate"));
+        assert_eq!(
+            path_crate_name("<my_crate::Parser as Default>::default"),
+            Some("my_crate"),
+        );
+        assert_eq!(path_crate_name("&my_crate::Parser"), Some("my_crate"));
+        assert_eq!(path_crate_name("main"), None);
+        assert_eq!(path_crate_name("<fn() as Default>::default"), None);
+        assert_eq!(path_crate_name("::parse"), None);
+    }
+
+    #[test]
+    fn frame_crate_names() {
+        let frame = |function: &str| BacktraceFrame {
+            function: function.to_string(),
+            file: None,
+            line: None,
+            column: None,
+        };
+        assert_eq!(frame("my_crate::parse").crate_name(), Some("my_crate"));
+        assert_eq!(
+            frame("<fn() as core::ops::FnOnce<()>>::call_once").crate_name(),
+            Some("core"),
+        );
+        assert_eq!(frame("main").crate_name(), None);
+        assert!(frame("std::rt::lang_start").is_runtime());
+        assert!(!frame("my_crate::parse").is_runtime());
+        assert!(!frame("main").is_runtime());
+    }
+
+    #[test]
+    fn omitted_frames() {
+        struct Frames(Vec<BacktraceFrame>, FrameFilter);
+
+        impl fmt::Display for Frames {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                let location = std::panic::Location::caller();
+                let palette = Palette::current();
+                fmt_frames(&self.0, location, self.1, None, &palette, f)
+            }
+        }
+
+        let frames = [
+            "std::backtrace::Backtrace::capture",
+            "my_crate::parse",
+            "core::ops::function::FnOnce::call_once",
+            "my_crate::load",
+            "std::rt::lang_start_internal",
+            "my_crate::run",
+            "main",
+            "_start",
+        ]
+        .map(|function| BacktraceFrame {
+            function: function.to_string(),
+            file: None,
+            line: None,
+            column: None,
+        })
+        .to_vec();
+        let filter = FrameFilter {
+            hide_runtime_frames: true,
+            max_frames: Some(2),
+        };
+        assert_eq!(
+            Frames(frames.clone(), filter).to_string(),
+            concat!(
+                "      ... 1 frame hidden\n",
+                "   1: my_crate::parse\n",
+                "      ... 1 frame hidden\n",
+                "   3: my_crate::load\n",
+                "      ... 1 frame hidden\n",
+                "      ... 2 more frames omitted\n",
+            ),
+        );
+        let filter = FrameFilter {
+            hide_runtime_frames: false,
+            max_frames: Some(6),
+        };
+        let rendered = Frames(frames, filter).to_string();
+        assert!(rendered.ends_with(concat!(
+            "   5: my_crate::run\n",
+            "      ... 2 more frames omitted\n",
+        )));
+    }
+}
@@ -0,0 +1,155 @@
+//! Labeled spans into inputs such as user-supplied files, which an error can
+//! point at in addition to the Rust source.
+
+use crate::color::{Color, Palette};
+use crate::snippet::{gutter, padding};
+use std::fmt::{self, Formatter};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// A named input buffer, e.g. the contents of a user-supplied config file.
+/// Cloning an `Input` is cheap, as the name and source are shared.
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Input {
+    name: Arc<str>,
+    source: Arc<str>,
+}
+
+impl Input {
+    /// An input called `name` (e.g. a file name) that consists of `source`.
+    pub fn new(name: impl Into<Arc<str>>, source: impl Into<Arc<str>>) -> Self {
+        Self {
+            name: name.into(),
+            source: source.into(),
+        }
+    }
+
+    /// The name of the input.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The contents of the input.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Input")
+            .field("name", &self.name)
+            .field("len", &self.source.len())
+            .finish()
+    }
+}
+
+/// A labeled byte range of an [`Input`].
+/// See [`ErrorCtx::add_span`](crate::ErrorCtx::add_span).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputSpan {
+    input: Input,
+    range: Range<usize>,
+    label: String,
+}
+
+impl InputSpan {
+    /// Label the byte `range` of `input`. The label may be empty.
+    pub fn new(
+        input: &Input,
+        range: Range<usize>,
+        label: impl Into<String>,
+    ) -> Self {
+        Self {
+            input: input.clone(),
+            range,
+            label: label.into(),
+        }
+    }
+
+    /// The input that the span points into.
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    /// The byte range of the span.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// The label of the span.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The byte range, clamped to the source and to char boundaries.
+    fn clamped_range(&self) -> Range<usize> {
+        let source = self.input.source();
+        let floor = |mut idx: usize| {
+            idx = idx.min(source.len());
+            while !source.is_char_boundary(idx) {
+                idx -= 1;
+            }
+            idx
+        };
+        let start = floor(self.range.start);
+        start..floor(self.range.end).max(start)
+    }
+}
+
+/// Write the lines of the input that `span` covers, with the covered part of
+/// each line underlined, and the label after the last underline:
+/// ```text
+///   --> config.toml:2:8
+///    |
+///  2 | port = "eighty"
+///    |        ^^^^^^^^ expected an integer
+/// ```
+pub(crate) fn fmt_span(
+    span: &InputSpan,
+    palette: &Palette,
+    f: &mut Formatter<'_>,
+) -> fmt::Result {
+    let source = span.input.source();
+    let Range { start, end } = span.clamped_range();
+    // The number of the line that contains the byte at `idx`:
+    let line_number = |idx: usize| {
+        let newlines = source.as_bytes()[..idx].iter().filter(|&&b| b == b'\n');
+        newlines.count() + 1
+    };
+    let first_number = line_number(start);
+    let last_number = line_number(if end > start { end - 1 } else { start });
+    let width = last_number.to_string().len();
+    let mut offset = source[..start].rfind('\n').map_or(0, |idx| idx + 1);
+    let column = source[offset..start].chars().count() + 1;
+    let arrow = palette.paint(Color::Blue, "-->");
+    let name = span.input.name();
+    writeln!(f, "{:width$}{arrow} {name}:{first_number}:{column}", "")?;
+    writeln!(f, "{}", gutter(None, width, palette))?;
+    for number in first_number..=last_number {
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |idx| offset + idx);
+        let text = source[offset..line_end].trim_end_matches('\r');
+        let numbered = gutter(Some(number), width, palette);
+        match text.trim_end() {
+            "" => writeln!(f, "{numbered}")?,
+            text => writeln!(f, "{numbered} {text}")?,
+        }
+        let from = start.max(offset).min(offset + text.len()) - offset;
+        let to = end.min(offset + text.len()).max(offset + from) - offset;
+        let underline = "^".repeat(text[from..to].chars().count().max(1));
+        let underline = palette.paint(Color::Red, underline);
+        let padding = padding(&text[..from]);
+        let gutter = gutter(None, width, palette);
+        write!(f, "{gutter} {padding}{underline}")?;
+        if number == last_number && !span.label.is_empty() {
+            write!(f, " {}", palette.paint(Color::Red, &span.label))?;
+        }
+        writeln!(f)?;
+        offset = line_end + 1;
+    }
+    Ok(())
+}
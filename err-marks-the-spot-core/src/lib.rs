@@ -1,42 +1,335 @@
-//!
+//! Runtime support for `#[err_marks_the_spot]`-annotated error types.
 
-use ansi_term::Color;
+mod capture;
+mod color;
+mod ext;
+mod frame;
+mod input;
+mod located;
+mod location;
+mod normalize;
+mod owned;
+mod render;
+mod report;
+mod snippet;
+mod thin;
+
+use crate::capture::CtxBacktrace;
+pub use crate::capture::{
+    BACKTRACE_CACHE_CAPACITY, CAPTURE_POLICY_ENV_VAR, CapturePolicy,
+    capture_policy, set_capture_policy,
+};
+use crate::color::{Color, Palette};
+pub use crate::color::{
+    ColorPolicy, color_policy, colors_enabled, set_color_policy,
+};
+pub use crate::ext::{OptionExt, ResultExt};
+pub use crate::frame::{
+    BacktraceFrame, FrameFilter, frame_filter, set_frame_filter,
+};
+pub use crate::input::{Input, InputSpan};
+pub use crate::located::Located;
+use crate::location::AnyLocation;
+pub use crate::location::{CtxLocation, SourceLocation};
+pub use crate::normalize::Normalized;
+pub use crate::owned::{CaptureStatus, OwnedErrorCtx};
+pub use crate::render::{
+    ErrorMessage, RenderStyle, Rendered, render_style, set_render_style,
+};
+pub use crate::report::{Report, ReportBacktrace};
+pub use crate::snippet::{
+    SourceSnippets, set_source_snippets, source_snippets,
+};
+pub use crate::thin::ThinErrorCtx;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::ops::Range;
+use std::panic::Location;
 
 #[derive(Debug)]
 pub struct ErrorCtx {
-    location: &'static std::panic::Location<'static>,
-    backtrace: std::backtrace::Backtrace,
+    location: CtxLocation,
+    backtrace: CtxBacktrace,
+    /// The rarely used parts, which are only allocated when they're used.
+    extras: Option<Box<CtxExtras>>,
+}
+
+#[derive(Debug, Default)]
+struct CtxExtras {
+    /// The locations that the error was propagated through, from the
+    /// innermost one to the outermost one.
+    trail: Vec<CtxLocation>,
+    /// Labeled spans into inputs such as user-supplied files.
+    spans: Vec<InputSpan>,
 }
 
 impl ErrorCtx {
+    /// Create a context for the caller's location, which captures a
+    /// backtrace according to the process-wide [`CapturePolicy`].
     #[track_caller]
     pub fn new() -> Self {
+        Self::with_capture_policy(capture_policy())
+    }
+
+    /// Create a context for the caller's location, which captures a
+    /// backtrace according to `policy`. A process-wide policy of
+    /// [`CapturePolicy::Off`] takes precedence over `policy`.
+    #[track_caller]
+    pub fn with_capture_policy(policy: CapturePolicy) -> Self {
+        Self::capture(CtxLocation::Static(Location::caller()), policy)
+    }
+
+    /// Create a context for `location`, which captures a backtrace according
+    /// to the process-wide [`CapturePolicy`], e.g. for the location of a
+    /// panic that a panic hook reports.
+    ///
+    /// The location is copied into an owned [`SourceLocation`], since it may
+    /// not be `'static`. A helper that is annotated with `#[track_caller]`
+    /// can call [`ErrorCtx::new()`] instead, which refers to the `'static`
+    /// location of the helper's caller without copying it.
+    pub fn from_location(location: &Location<'_>) -> Self {
+        let location = CtxLocation::from(SourceLocation::from(location));
+        Self::capture(location, capture_policy())
+    }
+
+    /// Create a context for the location `file:line:column` without
+    /// a backtrace, e.g. to build deterministic fixtures in tests.
+    pub fn at(file: impl Into<String>, line: u32, column: u32) -> Self {
+        let location = SourceLocation {
+            file: file.into(),
+            line,
+            column,
+        };
+        let backtrace = CtxBacktrace::Owned(Backtrace::disabled());
+        Self::from_parts(CtxLocation::from(location), backtrace)
+    }
+
+    /// Create a context for the caller's location without a backtrace, as
+    /// a stand-in for a context that isn't available, e.g. for an error that
+    /// was deserialized without one.
+    #[track_caller]
+    pub fn placeholder() -> Self {
+        Self::location_only(Location::caller())
+    }
+
+    /// Create a context for `location` without a backtrace.
+    pub(crate) fn location_only(location: &'static Location<'static>) -> Self {
+        let location = CtxLocation::Static(location);
+        Self::capture(location, CapturePolicy::LocationOnly)
+    }
+
+    fn capture(location: CtxLocation, policy: CapturePolicy) -> Self {
+        let policy = match capture_policy() {
+            CapturePolicy::Off => CapturePolicy::Off,
+            _ => policy,
+        };
+        let backtrace = CtxBacktrace::capture(policy, &location);
+        Self::from_parts(location, backtrace)
+    }
+
+    pub(crate) fn from_parts(
+        location: CtxLocation,
+        backtrace: CtxBacktrace,
+    ) -> Self {
         Self {
-            location: std::panic::Location::caller(),
-            backtrace: std::backtrace::Backtrace::capture(),
+            location,
+            backtrace,
+            extras: None,
         }
     }
+
+    /// The location where the error was created.
+    pub fn location(&self) -> &CtxLocation {
+        &self.location
+    }
+
+    /// The source file where the error was created.
+    pub fn file(&self) -> &str {
+        self.location.file()
+    }
+
+    /// The line in the source file where the error was created.
+    pub fn line(&self) -> u32 {
+        self.location.line()
+    }
+
+    /// The column in the source file where the error was created.
+    pub fn column(&self) -> u32 {
+        self.location.column()
+    }
+
+    /// The backtrace that was captured when the error was created.
+    /// Whether it was actually captured depends on the [`CapturePolicy`].
+    /// With [`CapturePolicy::Cached`], this backtrace is shared with
+    /// every other context created at the same location.
+    ///
+    /// NOTE: A context that was restored from an [`OwnedErrorCtx`] (e.g. by
+    ///       deserializing it) has only the resolved frames of its backtrace,
+    ///       so this returns a disabled backtrace. Use [`Self::frames`] and
+    ///       [`Self::backtrace_status`] instead, which return the restored
+    ///       frames and status.
+    pub fn backtrace(&self) -> &Backtrace {
+        self.backtrace.backtrace()
+    }
+
+    /// Whether the backtrace was captured.
+    pub fn backtrace_status(&self) -> BacktraceStatus {
+        self.backtrace.status()
+    }
+
+    /// Resolve the frames of the backtrace, from the innermost frame to the
+    /// outermost one. This is empty if the backtrace wasn't captured.
+    pub fn frames(&self) -> Vec<BacktraceFrame> {
+        self.backtrace.frames()
+    }
+
+    /// The locations that the error was propagated through after it was
+    /// created, from the innermost one to the outermost one.
+    /// See [`ResultExt::trace`].
+    pub fn trail(&self) -> &[CtxLocation] {
+        self.extras.as_ref().map_or(&[], |extras| &extras.trail)
+    }
+
+    /// Record that the error was propagated through the caller's location.
+    /// This doesn't capture a backtrace.
+    #[track_caller]
+    pub fn trace(&mut self) {
+        let location = CtxLocation::Static(Location::caller());
+        self.extras_mut().trail.push(location);
+    }
+
+    /// The labeled spans into inputs that the error is about, e.g. the parts
+    /// of a user-supplied config file that are invalid.
+    pub fn spans(&self) -> &[InputSpan] {
+        self.extras.as_ref().map_or(&[], |extras| &extras.spans)
+    }
+
+    /// Label the byte `range` of `input`. The span is rendered with the lines
+    /// of the input that it covers, after the location of the error.
+    pub fn add_span(
+        &mut self,
+        input: &Input,
+        range: Range<usize>,
+        label: impl Into<String>,
+    ) {
+        let span = InputSpan::new(input, range, label);
+        self.extras_mut().spans.push(span);
+    }
+
+    fn extras_mut(&mut self) -> &mut CtxExtras {
+        self.extras.get_or_insert_default()
+    }
+
+    /// Write the location as `file:line:column`.
+    pub(crate) fn fmt_location(
+        &self,
+        palette: &Palette,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{}", palette.paint_location(&self.location))
+    }
+}
+
+impl Default for ErrorCtx {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A zero-sized stand-in for [`ErrorCtx`], which is used as the type of the
+/// context field of types annotated with
+/// `#[err_marks_the_spot(feature = "...", disabled_ctx = "zero_sized")]`
+/// when the feature is disabled.
+///
+/// Because the field exists in both configurations, struct expressions
+/// such as `MyError { f0, ctx: Default::default() }` and patterns such as
+/// `MyError { f0, ctx }` compile regardless of the feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NoErrorCtx;
+
+impl NoErrorCtx {
+    #[track_caller]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl std::fmt::Display for NoErrorCtx {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Ok(())
+    }
+}
+
+/// Uniform access to the [`ErrorCtx`] of an error type.
+///
+/// This trait is implemented by `#[err_marks_the_spot]` for every annotated
+/// type. When the type is annotated with a `feature = "..."` argument and the
+/// feature is disabled, the type has no `ErrorCtx`, and each method returns
+/// `None`. This means that callers don't need any `#[cfg]` attributes.
+pub trait HasErrorCtx {
+    /// Return a reference to the `ErrorCtx`, if any.
+    fn ctx(&self) -> Option<&ErrorCtx>;
+
+    /// Return a mutable reference to the `ErrorCtx`, if any.
+    fn ctx_mut(&mut self) -> Option<&mut ErrorCtx>;
+
+    /// Consume `self` and return its `ErrorCtx`, if any.
+    fn take_ctx(self) -> Option<ErrorCtx>
+    where
+        Self: Sized;
 }
 
-#[rustfmt::skip]
 impl std::fmt::Display for ErrorCtx {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Self { location, backtrace } = self;
-        let error = Color::Red.paint("ERROR");
-        let file = {
-            let file = location.file();
-            Color::Blue.paint(format!("{file}"))
-        };
-        let line = {
-            let line = location.line();
-            Color::Green.paint(format!("{line}"))
+        let frames;
+        let backtrace = match CaptureStatus::from(self.backtrace_status()) {
+            CaptureStatus::Captured => {
+                frames = self.frames();
+                Ok(&*frames)
+            }
+            status => Err(status),
         };
-        let column = {
-            let column = location.column();
-            Color::Yellow.paint(format!("{column}"))
-        };
-        writeln!(f, "{error} detected @ {file}:{line}:{column}:")?;
-        writeln!(f, "{backtrace}")?;
-        Ok(())
+        fmt_ctx(&self.location, self.trail(), self.spans(), backtrace, f)
+    }
+}
+
+/// Write an `ErrorCtx` (or an `OwnedErrorCtx`) with the given parts. When the
+/// backtrace wasn't captured, its status is written instead, like the
+/// `Display` impl of `Backtrace` does.
+#[rustfmt::skip]
+pub(crate) fn fmt_ctx(
+    location: &impl AnyLocation,
+    trail: &[impl AnyLocation],
+    spans: &[InputSpan],
+    backtrace: Result<&[BacktraceFrame], CaptureStatus>,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    let palette = Palette::current();
+    let error = palette.paint(Color::Red, "ERROR");
+    let painted = palette.paint_location(location);
+    writeln!(f, "{error} detected @ {painted}:")?;
+    let snippets = source_snippets();
+    if let Some(snippets) = snippets {
+        let (line, column) = (location.line(), location.column());
+        let file = location.file();
+        snippet::fmt_snippet(file, line, column, snippets, "", &palette, f)?;
+    }
+    for span in spans {
+        input::fmt_span(span, &palette, f)?;
+    }
+    for location in trail {
+        let location = palette.paint_location(location);
+        writeln!(f, "  propagated @ {location}")?;
+    }
+    if normalize::normalizing() {
+        return writeln!(f, "{}", normalize::BACKTRACE_MARKER);
+    }
+    match backtrace {
+        Ok(frames) => {
+            let filter = frame_filter();
+            frame::fmt_frames(frames, location, filter, snippets, &palette, f)
+        }
+        Err(CaptureStatus::Unsupported) => writeln!(f, "unsupported backtrace"),
+        Err(_) => writeln!(f, "disabled backtrace"),
     }
 }
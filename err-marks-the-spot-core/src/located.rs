@@ -0,0 +1,87 @@
+//! Located errors for error types that aren't annotated with
+//! `#[err_marks_the_spot]`, e.g. those of other crates.
+
+use crate::{ErrorCtx, ErrorMessage, HasErrorCtx, RenderStyle};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// An error of any type, paired with the [`ErrorCtx`] of the location where
+/// it was wrapped. This makes errors of types that cannot be annotated with
+/// `#[err_marks_the_spot]`, such as `std::io::Error`, located as well.
+///
+/// The `Display` impl renders the wrapped error like the `Display` impls of
+/// annotated types render their message, and `Error::source()` returns the
+/// source of the wrapped error.
+#[derive(Debug)]
+pub struct Located<E> {
+    error: E,
+    ctx: ErrorCtx,
+}
+
+impl<E> Located<E> {
+    /// Wrap `error`, recording the caller's location.
+    #[track_caller]
+    pub fn new(error: E) -> Self {
+        Self { error, ctx: ErrorCtx::new() }
+    }
+
+    /// The wrapped error.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// The wrapped error, mutably.
+    pub fn error_mut(&mut self) -> &mut E {
+        &mut self.error
+    }
+
+    /// Unwrap the error, discarding its context.
+    pub fn into_error(self) -> E {
+        self.error
+    }
+
+    /// Unwrap the error and its context.
+    pub fn into_parts(self) -> (E, ErrorCtx) {
+        (self.error, self.ctx)
+    }
+}
+
+/// Wrapping an error with `?` records the location of the `?`.
+impl<E> From<E> for Located<E> {
+    #[track_caller]
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+impl<E> HasErrorCtx for Located<E> {
+    fn ctx(&self) -> Option<&ErrorCtx> {
+        Some(&self.ctx)
+    }
+
+    fn ctx_mut(&mut self) -> Option<&mut ErrorCtx> {
+        Some(&mut self.ctx)
+    }
+
+    fn take_ctx(self) -> Option<ErrorCtx> {
+        Some(self.ctx)
+    }
+}
+
+impl<E: Display> ErrorMessage for Located<E> {
+    fn fmt_message(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl<E: Display> Display for Located<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        RenderStyle::select(f, None).fmt_error(self, f)
+    }
+}
+
+impl<E: Error> Error for Located<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
@@ -0,0 +1,196 @@
+//! The locations that `ErrorCtx` output points at.
+
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::panic::Location;
+use std::sync::Arc;
+
+/// The location of an [`ErrorCtx`](crate::ErrorCtx), or of an entry of its
+/// propagation trail.
+///
+/// Locations are usually captured from the caller with `#[track_caller]`, in
+/// which case they borrow the `'static` location. They can also be owned, e.g.
+/// when they were deserialized, created with [`ErrorCtx::at`], or created from
+/// a `&Location` that may not be `'static` with [`ErrorCtx::from_location`].
+/// Both kinds render the same, and compare equal when their file, line and
+/// column are equal.
+///
+/// [`ErrorCtx::at`]: crate::ErrorCtx::at
+/// [`ErrorCtx::from_location`]: crate::ErrorCtx::from_location
+#[derive(Clone, Debug)]
+pub enum CtxLocation {
+    /// A location that was captured with `#[track_caller]`.
+    Static(&'static Location<'static>),
+    /// An owned location.
+    Owned(Arc<SourceLocation>),
+}
+
+impl CtxLocation {
+    /// The source file.
+    pub fn file(&self) -> &str {
+        match self {
+            Self::Static(location) => location.file(),
+            Self::Owned(location) => &location.file,
+        }
+    }
+
+    /// The line in the source file.
+    pub fn line(&self) -> u32 {
+        match self {
+            Self::Static(location) => location.line(),
+            Self::Owned(location) => location.line,
+        }
+    }
+
+    /// The column in the source file.
+    pub fn column(&self) -> u32 {
+        match self {
+            Self::Static(location) => location.column(),
+            Self::Owned(location) => location.column,
+        }
+    }
+
+    /// The `&'static Location`, if this location was captured.
+    pub fn as_static(&self) -> Option<&'static Location<'static>> {
+        match self {
+            Self::Static(location) => Some(location),
+            Self::Owned(_) => None,
+        }
+    }
+}
+
+impl From<&'static Location<'static>> for CtxLocation {
+    fn from(location: &'static Location<'static>) -> Self {
+        Self::Static(location)
+    }
+}
+
+impl From<SourceLocation> for CtxLocation {
+    fn from(location: SourceLocation) -> Self {
+        Self::Owned(Arc::new(location))
+    }
+}
+
+impl PartialEq for CtxLocation {
+    fn eq(&self, other: &Self) -> bool {
+        (self.file(), self.line(), self.column())
+            == (other.file(), other.line(), other.column())
+    }
+}
+
+impl Eq for CtxLocation {}
+
+impl Hash for CtxLocation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.file(), self.line(), self.column()).hash(state);
+    }
+}
+
+impl Display for CtxLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file(), self.line(), self.column())
+    }
+}
+
+/// An owned location in a source file.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceLocation {
+    /// The source file.
+    pub file: String,
+    /// The line in the source file.
+    pub line: u32,
+    /// The column in the source file.
+    pub column: u32,
+}
+
+impl From<&Location<'_>> for SourceLocation {
+    fn from(location: &Location<'_>) -> Self {
+        Self {
+            file: location.file().to_string(),
+            line: location.line(),
+            column: location.column(),
+        }
+    }
+}
+
+impl From<&CtxLocation> for SourceLocation {
+    fn from(location: &CtxLocation) -> Self {
+        match location {
+            CtxLocation::Static(location) => Self::from(*location),
+            CtxLocation::Owned(location) => SourceLocation::clone(location),
+        }
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// A location that `ErrorCtx` output can point at: a `&'static Location`,
+/// a [`SourceLocation`] or a [`CtxLocation`].
+pub(crate) trait AnyLocation {
+    fn file(&self) -> &str;
+
+    fn line(&self) -> u32;
+
+    fn column(&self) -> u32;
+}
+
+impl AnyLocation for Location<'_> {
+    fn file(&self) -> &str {
+        Location::file(self)
+    }
+
+    fn line(&self) -> u32 {
+        Location::line(self)
+    }
+
+    fn column(&self) -> u32 {
+        Location::column(self)
+    }
+}
+
+impl AnyLocation for SourceLocation {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    fn line(&self) -> u32 {
+        self.line
+    }
+
+    fn column(&self) -> u32 {
+        self.column
+    }
+}
+
+impl AnyLocation for CtxLocation {
+    fn file(&self) -> &str {
+        CtxLocation::file(self)
+    }
+
+    fn line(&self) -> u32 {
+        CtxLocation::line(self)
+    }
+
+    fn column(&self) -> u32 {
+        CtxLocation::column(self)
+    }
+}
+
+impl<L: AnyLocation + ?Sized> AnyLocation for &L {
+    fn file(&self) -> &str {
+        (**self).file()
+    }
+
+    fn line(&self) -> u32 {
+        (**self).line()
+    }
+
+    fn column(&self) -> u32 {
+        (**self).column()
+    }
+}
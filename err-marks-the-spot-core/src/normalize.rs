@@ -0,0 +1,99 @@
+//! Deterministic rendering of `ErrorCtx` output, e.g. for snapshot tests.
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// What the backtrace of an `ErrorCtx` is replaced with in normalized output.
+pub(crate) const BACKTRACE_MARKER: &str = "<backtrace>";
+
+/// Renders a value, e.g. an annotated error, an `ErrorCtx` or a
+/// [`Report`](crate::Report), so that the output is the same on every
+/// machine. This is meant for snapshot tests:
+/// ```ignore
+/// let full = error.render(RenderStyle::Full);
+/// insta::assert_snapshot!(Normalized::new(&full).to_string());
+/// ```
+/// While the value is rendered:
+/// - The output is never colored, regardless of the [`ColorPolicy`].
+/// - Absolute paths of locations in the workspace are made relative to the
+///   workspace, and path separators are always `/`.
+/// - The backtrace of each `ErrorCtx` is replaced with a `<backtrace>` line,
+///   whether or not it was captured.
+///
+/// This only affects the current thread, so tests that run in parallel
+/// don't interfere with each other.
+///
+/// [`ColorPolicy`]: crate::ColorPolicy
+pub struct Normalized<'a, T: ?Sized> {
+    value: &'a T,
+}
+
+impl<'a, T: Display + ?Sized> Normalized<'a, T> {
+    /// Render `value` deterministically.
+    pub fn new(value: &'a T) -> Self {
+        Self { value }
+    }
+}
+
+impl<T: Display + ?Sized> Display for Normalized<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        struct Guard(bool);
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                NORMALIZING.set(self.0);
+            }
+        }
+
+        let _guard = Guard(NORMALIZING.replace(true));
+        self.value.fmt(f)
+    }
+}
+
+impl<T: Display + ?Sized> fmt::Debug for Normalized<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+thread_local! {
+    static NORMALIZING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether output is currently rendered by a [`Normalized`].
+pub(crate) fn normalizing() -> bool {
+    NORMALIZING.get()
+}
+
+/// Normalize the path of a location, if output is currently normalized.
+pub(crate) fn normalize_path(file: &str) -> Cow<'_, str> {
+    if !normalizing() {
+        return Cow::Borrowed(file);
+    }
+    let path = Path::new(file);
+    let relative = workspace_root()
+        .and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path);
+    Cow::Owned(relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// The root of the workspace, which is the nearest directory with a
+/// `Cargo.lock` above the package (or the working directory).
+static WORKSPACE_ROOT: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    let dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())?;
+    let root = dir
+        .ancestors()
+        .find(|dir| dir.join("Cargo.lock").is_file())
+        .unwrap_or(&dir);
+    Some(root.to_path_buf())
+});
+
+/// The root of the workspace, see [`WORKSPACE_ROOT`].
+pub(crate) fn workspace_root() -> Option<&'static Path> {
+    WORKSPACE_ROOT.as_deref()
+}
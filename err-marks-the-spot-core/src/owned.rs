@@ -0,0 +1,183 @@
+//! An owned form of [`ErrorCtx`], which can be serialized, sent elsewhere
+//! (e.g. to a log pipeline), deserialized and rendered again.
+
+use crate::capture::CtxBacktrace;
+use crate::{BacktraceFrame, CtxLocation, ErrorCtx, InputSpan, SourceLocation};
+use std::backtrace::BacktraceStatus;
+use std::fmt::{self, Display, Formatter};
+
+/// An owned copy of an [`ErrorCtx`], which renders the same `Display` output.
+///
+/// With the `serde` feature, this type and `ErrorCtx` serialize to the same
+/// schema, e.g. in JSON:
+/// ```json
+/// {
+///   "location": { "file": "src/config.rs", "line": 12, "column": 9 },
+///   "backtrace_status": "captured",
+///   "frames": [
+///     {
+///       "function": "my_crate::config::parse",
+///       "file": "./src/config.rs",
+///       "line": 12,
+///       "column": 9
+///     }
+///   ],
+///   "trail": [{ "file": "src/main.rs", "line": 8, "column": 18 }],
+///   "spans": [
+///     {
+///       "input": { "name": "config.toml", "source": "port = \"eighty\"\n" },
+///       "range": { "start": 7, "end": 15 },
+///       "label": "expected an integer"
+///     }
+///   ]
+/// }
+/// ```
+/// - `backtrace_status` is one of `captured`, `disabled` or `unsupported`.
+/// - `frames` is empty unless the backtrace was captured, and the `file`,
+///   `line` and `column` of a frame are `null` when they're unknown.
+/// - `trail` and `spans` may be omitted when deserializing, and default to
+///   being empty.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedErrorCtx {
+    /// The location where the error was created.
+    pub location: SourceLocation,
+    /// Whether the backtrace was captured.
+    pub backtrace_status: CaptureStatus,
+    /// The frames of the backtrace, from the innermost frame to the
+    /// outermost one.
+    pub frames: Vec<BacktraceFrame>,
+    /// See [`ErrorCtx::trail`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub trail: Vec<SourceLocation>,
+    /// See [`ErrorCtx::spans`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub spans: Vec<InputSpan>,
+}
+
+impl From<&ErrorCtx> for OwnedErrorCtx {
+    fn from(ctx: &ErrorCtx) -> Self {
+        Self {
+            location: SourceLocation::from(ctx.location()),
+            backtrace_status: CaptureStatus::from(ctx.backtrace_status()),
+            frames: ctx.frames(),
+            trail: ctx.trail().iter().map(SourceLocation::from).collect(),
+            spans: ctx.spans().to_vec(),
+        }
+    }
+}
+
+impl From<ErrorCtx> for OwnedErrorCtx {
+    fn from(ctx: ErrorCtx) -> Self {
+        Self::from(&ctx)
+    }
+}
+
+impl From<OwnedErrorCtx> for ErrorCtx {
+    /// Restore a context, which renders and serializes like `owned`.
+    fn from(owned: OwnedErrorCtx) -> Self {
+        let OwnedErrorCtx {
+            location,
+            backtrace_status,
+            frames,
+            trail,
+            spans,
+        } = owned;
+        let mut ctx = ErrorCtx::from_parts(
+            CtxLocation::from(location),
+            CtxBacktrace::Restored(backtrace_status, frames),
+        );
+        if !trail.is_empty() || !spans.is_empty() {
+            let extras = ctx.extras_mut();
+            extras.trail = trail.into_iter().map(CtxLocation::from).collect();
+            extras.spans = spans;
+        }
+        ctx
+    }
+}
+
+impl Display for OwnedErrorCtx {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let backtrace = match self.backtrace_status {
+            CaptureStatus::Captured => Ok(&*self.frames),
+            status => Err(status),
+        };
+        crate::fmt_ctx(&self.location, &self.trail, &self.spans, backtrace, f)
+    }
+}
+
+/// Whether a backtrace was captured, like [`BacktraceStatus`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CaptureStatus {
+    /// The backtrace was captured.
+    Captured,
+    /// Capturing a backtrace was disabled.
+    Disabled,
+    /// Capturing a backtrace isn't supported on the platform.
+    Unsupported,
+}
+
+impl From<BacktraceStatus> for CaptureStatus {
+    fn from(status: BacktraceStatus) -> Self {
+        match status {
+            BacktraceStatus::Captured => Self::Captured,
+            BacktraceStatus::Disabled => Self::Disabled,
+            _ => Self::Unsupported,
+        }
+    }
+}
+
+impl From<CaptureStatus> for BacktraceStatus {
+    fn from(status: CaptureStatus) -> Self {
+        match status {
+            CaptureStatus::Captured => Self::Captured,
+            CaptureStatus::Disabled => Self::Disabled,
+            CaptureStatus::Unsupported => Self::Unsupported,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ErrorCtx {
+    /// Serialize to the schema of [`OwnedErrorCtx`].
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        OwnedErrorCtx::from(self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for crate::ThinErrorCtx {
+    /// Serialize to the schema of [`OwnedErrorCtx`].
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.as_ctx().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ErrorCtx {
+    /// Deserialize from the schema of [`OwnedErrorCtx`] into a restored
+    /// context, which renders and serializes like the original one.
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        OwnedErrorCtx::deserialize(deserializer).map(ErrorCtx::from)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for crate::ThinErrorCtx {
+    /// Deserialize like [`ErrorCtx`].
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        ErrorCtx::deserialize(deserializer).map(Self::from)
+    }
+}
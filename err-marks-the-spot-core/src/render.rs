@@ -0,0 +1,175 @@
+//! Render styles for `#[err_marks_the_spot]`-annotated error types.
+
+use crate::HasErrorCtx;
+use crate::color::Palette;
+use crate::report;
+use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How an annotated error type is rendered by its `Display` impl.
+///
+/// The style of a type is, in order of precedence:
+/// 1. the style of the error whose message it is rendered in, if it is
+///    nested in one, e.g. through a `{0}` placeholder (see below),
+/// 2. [`RenderStyle::SingleLine`] when formatted with `{:#}`,
+/// 3. the style given by its `render = "..."` attribute argument,
+/// 4. the process-wide style set with [`set_render_style`].
+///
+/// Nested errors are rendered in the same style as the error around them,
+/// except that they are rendered as a [`RenderStyle::Message`] within a
+/// [`RenderStyle::SingleLine`], so that the line has a single location.
+///
+/// So by default, `{}` renders only the message and `{:#}` renders the
+/// message followed by its location. The full `ErrorCtx` is only rendered
+/// in an explicit [`RenderStyle::Full`], e.g. with
+/// `error.render(RenderStyle::Full)`, or by a [`Report`](crate::Report).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RenderStyle {
+    /// Only the message i.e. the docstring with its placeholders filled in.
+    /// This is the default.
+    #[default]
+    Message,
+    /// The message on a single line, followed by ` @ file:line:column`.
+    SingleLine,
+    /// The message, an empty line, and then the full `ErrorCtx`,
+    /// including the backtrace.
+    Full,
+}
+
+static RENDER_STYLE: AtomicU8 = AtomicU8::new(RenderStyle::Message as u8);
+
+/// Set the process-wide [`RenderStyle`].
+pub fn set_render_style(style: RenderStyle) {
+    RENDER_STYLE.store(style as u8, Ordering::Relaxed);
+}
+
+/// Get the process-wide [`RenderStyle`].
+pub fn render_style() -> RenderStyle {
+    match RENDER_STYLE.load(Ordering::Relaxed) {
+        0 => RenderStyle::Message,
+        1 => RenderStyle::SingleLine,
+        _ => RenderStyle::Full,
+    }
+}
+
+impl RenderStyle {
+    /// Select the style for formatting with `f`, given the style of the type
+    /// (if it has one). This is used by the generated `Display` impls.
+    pub fn select(f: &Formatter<'_>, type_style: Option<Self>) -> Self {
+        if let Some(style) = NESTED_STYLE.get() {
+            style
+        } else if f.alternate() {
+            Self::SingleLine
+        } else {
+            type_style.unwrap_or_else(render_style)
+        }
+    }
+
+    /// Render `error` in this style.
+    pub fn fmt_error<E>(self, error: &E, f: &mut Formatter<'_>) -> fmt::Result
+    where
+        E: ErrorMessage + ?Sized,
+    {
+        if let Some(result) = report::fmt_probed(error, f) {
+            return result;
+        }
+        let message = Message { error, style: self };
+        let ctx = error.ctx();
+        match self {
+            Self::Message => write!(f, "{message}"),
+            Self::SingleLine => {
+                let message = message.to_string();
+                let mut lines = message.lines().map(str::trim);
+                if let Some(first) = lines.next() {
+                    write!(f, "{first}")?;
+                }
+                for line in lines.filter(|line| !line.is_empty()) {
+                    write!(f, " {line}")?;
+                }
+                if let Some(ctx) = ctx {
+                    write!(f, " @ ")?;
+                    ctx.fmt_location(&Palette::current(), f)?;
+                }
+                Ok(())
+            }
+            Self::Full => {
+                writeln!(f, "{message}")?;
+                if let Some(ctx) = ctx {
+                    writeln!(f)?;
+                    writeln!(f, "{ctx}")?;
+                    error.fmt_details(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The message of an error type, which can be rendered in any [`RenderStyle`].
+///
+/// This trait is implemented by `#[err_marks_the_spot]` for every annotated
+/// type, and its `Display` impl is built on top of it.
+pub trait ErrorMessage: HasErrorCtx {
+    /// Write only the message, without a trailing newline.
+    fn fmt_message(&self, f: &mut Formatter<'_>) -> fmt::Result;
+
+    /// Write details that are rendered after the `ErrorCtx` in
+    /// [`RenderStyle::Full`], e.g. the context stack of an `AnyError`.
+    /// By default, there are none.
+    fn fmt_details(&self, _f: &mut Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Render `self` in a specific style, regardless of the style of the
+    /// type or the process-wide style, e.g. `error.render(RenderStyle::Full)`.
+    fn render(&self, style: RenderStyle) -> Rendered<'_, Self> {
+        Rendered { error: self, style }
+    }
+}
+
+/// An error rendered in a specific [`RenderStyle`].
+/// See [`ErrorMessage::render`].
+pub struct Rendered<'e, E: ?Sized> {
+    error: &'e E,
+    style: RenderStyle,
+}
+
+impl<E: ErrorMessage + ?Sized> Display for Rendered<'_, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.style.fmt_error(self.error, f)
+    }
+}
+
+thread_local! {
+    /// The style that nested errors are rendered in, while the message of an
+    /// error is rendered.
+    static NESTED_STYLE: Cell<Option<RenderStyle>> = const { Cell::new(None) };
+}
+
+/// The message of an error that is rendered in `style`.
+struct Message<'e, E: ?Sized> {
+    error: &'e E,
+    style: RenderStyle,
+}
+
+impl<E: ErrorMessage + ?Sized> Display for Message<'_, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        struct Guard(Option<RenderStyle>);
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                NESTED_STYLE.set(self.0);
+            }
+        }
+
+        let nested_style = match self.style {
+            RenderStyle::Message | RenderStyle::SingleLine => {
+                RenderStyle::Message
+            }
+            RenderStyle::Full => RenderStyle::Full,
+        };
+        let _guard = Guard(NESTED_STYLE.replace(Some(nested_style)));
+        self.error.fmt_message(f)
+    }
+}
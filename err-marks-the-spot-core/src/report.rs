@@ -0,0 +1,214 @@
+//! Rendering of a whole error chain, without duplicated backtraces.
+
+use crate::color::Palette;
+use crate::location::CtxLocation;
+use crate::render::ErrorMessage;
+use std::backtrace::BacktraceStatus;
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// Renders an error and its chain of sources, e.g. with `eprintln!("{}",
+/// Report::new(&error))`.
+///
+/// The `Display` impls of annotated types embed the `Display` of the errors
+/// in their message, each with its own `ErrorCtx`. A `Report` instead renders
+/// the message and location of each layer of the chain on a single line, and
+/// only the `ErrorCtx` of one layer (see [`ReportBacktrace`]):
+/// ```text
+/// Container: Blah: Blah failed @ src/lib.rs:30:17
+///   caused by: Blah: Blah failed @ src/lib.rs:24:17
+///   caused by: Blah failed @ src/lib.rs:12:5
+///
+/// ERROR detected @ src/lib.rs:12:5:
+/// ...
+/// ```
+#[derive(Clone, Copy)]
+pub struct Report<'e> {
+    error: &'e (dyn Error + 'static),
+    backtrace: ReportBacktrace,
+}
+
+/// Which layer of the error chain a [`Report`] renders the `ErrorCtx` of,
+/// including its backtrace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ReportBacktrace {
+    /// The deepest layer that captured a backtrace or, if no layer did,
+    /// the deepest layer with an `ErrorCtx`.
+    #[default]
+    Deepest,
+    /// The layer at this depth, where the error itself is at depth 0.
+    Layer(usize),
+    /// No layer.
+    Hidden,
+}
+
+impl<'e> Report<'e> {
+    /// Report `error` and its chain of sources.
+    pub fn new(error: &'e (dyn Error + 'static)) -> Self {
+        Self {
+            error,
+            backtrace: ReportBacktrace::default(),
+        }
+    }
+
+    /// Choose the layer that the `ErrorCtx` is rendered of.
+    pub fn backtrace(mut self, backtrace: ReportBacktrace) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
+    /// The layers of the error chain, from the error itself to its deepest
+    /// source.
+    fn layers(&self) -> impl Iterator<Item = &'e (dyn Error + 'static)> {
+        std::iter::successors(Some(self.error), |&error| error.source())
+    }
+}
+
+impl Display for Report<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let palette = Palette::current();
+        let mut deepest_captured = None;
+        let mut deepest = None;
+        for (depth, layer) in self.layers().enumerate() {
+            let (message, ctx) = probe(layer, ProbeMode::Message);
+            if depth > 0 {
+                write!(f, "\n  caused by: ")?;
+            }
+            let mut lines = message.lines().map(str::trim);
+            if let Some(first) = lines.next() {
+                write!(f, "{first}")?;
+            }
+            for line in lines.filter(|line| !line.is_empty()) {
+                write!(f, " {line}")?;
+            }
+            let Some(ctx) = ctx else {
+                continue;
+            };
+            write!(f, " @ {}", palette.paint_location(&ctx.location))?;
+            deepest = Some(layer);
+            if ctx.captured {
+                deepest_captured = Some(layer);
+            }
+        }
+        writeln!(f)?;
+        let layer = match self.backtrace {
+            ReportBacktrace::Deepest => deepest_captured.or(deepest),
+            ReportBacktrace::Layer(depth) => self.layers().nth(depth),
+            ReportBacktrace::Hidden => None,
+        };
+        if let Some(layer) = layer {
+            let (ctx, layer_ctx) = probe(layer, ProbeMode::Ctx);
+            if layer_ctx.is_some() && !ctx.is_empty() {
+                write!(f, "\n{ctx}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Report<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// What an annotated error renders while it is probed as a layer of a report.
+#[derive(Clone, Copy)]
+enum ProbeMode {
+    /// Only the message. Errors nested in the message render only their
+    /// message as well.
+    Message,
+    /// Only the `ErrorCtx`.
+    Ctx,
+}
+
+struct Probe {
+    mode: ProbeMode,
+    /// The address of the `Formatter` that the probed layer is rendered with.
+    /// Errors nested in its message are rendered with `Formatter`s of their
+    /// own, so this tells the layer itself apart from errors in its message,
+    /// e.g. when the layer isn't annotated but embeds an annotated source.
+    formatter: *const (),
+    /// The ctx of the probed layer, once the layer has been rendered.
+    layer: Option<Option<LayerCtx>>,
+}
+
+struct LayerCtx {
+    location: CtxLocation,
+    captured: bool,
+}
+
+thread_local! {
+    static PROBE: Cell<Option<Probe>> = const { Cell::new(None) };
+}
+
+/// Render `layer` in `mode`, and return the result along with the ctx of the
+/// layer (if it is an annotated error, and has an `ErrorCtx`).
+fn probe(
+    layer: &(dyn Error + 'static),
+    mode: ProbeMode,
+) -> (String, Option<LayerCtx>) {
+    struct Guard(Option<Probe>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            PROBE.set(self.0.take());
+        }
+    }
+
+    struct Probed<'e> {
+        layer: &'e (dyn Error + 'static),
+        mode: ProbeMode,
+    }
+
+    impl Display for Probed<'_> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            PROBE.set(Some(Probe {
+                mode: self.mode,
+                formatter: formatter_addr(f),
+                layer: None,
+            }));
+            Display::fmt(self.layer, f)
+        }
+    }
+
+    let guard = Guard(PROBE.take());
+    let rendered = Probed { layer, mode }.to_string();
+    let ctx = PROBE.take().and_then(|probe| probe.layer).flatten();
+    drop(guard);
+    (rendered, ctx)
+}
+
+/// Render `error` as (part of) a layer that a `Report` probes, if any.
+/// This is used by [`RenderStyle::fmt_error`](crate::RenderStyle::fmt_error).
+pub(crate) fn fmt_probed<E>(
+    error: &E,
+    f: &mut Formatter<'_>,
+) -> Option<fmt::Result>
+where
+    E: ErrorMessage + ?Sized,
+{
+    let mut probe = PROBE.take()?;
+    let mode = probe.mode;
+    if probe.layer.is_some() || probe.formatter != formatter_addr(f) {
+        // An error that is nested in the message of the probed layer:
+        PROBE.set(Some(probe));
+        return Some(error.fmt_message(f));
+    }
+    let ctx = error.ctx();
+    probe.layer = Some(ctx.map(|ctx| LayerCtx {
+        location: ctx.location().clone(),
+        captured: ctx.backtrace_status() == BacktraceStatus::Captured,
+    }));
+    PROBE.set(Some(probe));
+    Some(match (mode, ctx) {
+        (ProbeMode::Message, _) => error.fmt_message(f),
+        (ProbeMode::Ctx, Some(ctx)) => write!(f, "{ctx}"),
+        (ProbeMode::Ctx, None) => Ok(()),
+    })
+}
+
+fn formatter_addr(f: &Formatter<'_>) -> *const () {
+    std::ptr::from_ref(f).cast()
+}
@@ -0,0 +1,180 @@
+//! Snippets of source code around the locations in `ErrorCtx` output.
+
+use crate::color::{Color, Palette};
+use crate::normalize;
+use std::fmt::{self, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+
+/// How `ErrorCtx` output shows the source code around locations, like rustc
+/// diagnostics do:
+/// ```text
+/// ERROR detected @ src/lib.rs:12:9:
+///    |
+/// 11 | fn parse() -> Result<(), ParseError> {
+/// 12 |     Err(ParseError::new())
+///    |         ^
+/// 13 | }
+/// ```
+/// Source files are read from disk when the output is rendered. When a
+/// source file cannot be found, its location is rendered without a snippet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SourceSnippets {
+    /// The number of lines that are shown before and after the line of
+    /// a location.
+    pub context_lines: usize,
+    /// The number of backtrace frames that are shown with a snippet, in
+    /// addition to the location where the error was created. Only frames
+    /// that aren't runtime frames (see
+    /// [`BacktraceFrame::is_runtime`](crate::BacktraceFrame::is_runtime))
+    /// get a snippet, from the innermost one to the outermost one.
+    pub frames: usize,
+}
+
+impl SourceSnippets {
+    /// Show 2 lines around the location where the error was created, and no
+    /// snippets for backtrace frames.
+    pub const fn new() -> Self {
+        Self {
+            context_lines: 2,
+            frames: 0,
+        }
+    }
+}
+
+impl Default for SourceSnippets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static SOURCE_SNIPPETS: RwLock<Option<SourceSnippets>> = RwLock::new(None);
+
+/// Set the process-wide [`SourceSnippets`], or disable snippets with `None`
+/// (which is the default).
+pub fn set_source_snippets(snippets: Option<SourceSnippets>) {
+    *SOURCE_SNIPPETS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = snippets;
+}
+
+/// Get the process-wide [`SourceSnippets`], if snippets are enabled.
+pub fn source_snippets() -> Option<SourceSnippets> {
+    *SOURCE_SNIPPETS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Write the lines around `line` of `file`, with a caret under `column`.
+/// Each line is prefixed with `indent`. Nothing is written if the file
+/// cannot be read, or if it doesn't contain the line.
+pub(crate) fn fmt_snippet(
+    file: &str,
+    line: u32,
+    column: u32,
+    snippets: SourceSnippets,
+    indent: &str,
+    palette: &Palette,
+    f: &mut Formatter<'_>,
+) -> fmt::Result {
+    let Some(source) = find_source_file(file)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+    else {
+        return Ok(());
+    };
+    let lines: Vec<&str> = source.lines().collect();
+    let Some(idx) = (line as usize)
+        .checked_sub(1)
+        .filter(|&idx| idx < lines.len())
+    else {
+        return Ok(());
+    };
+    let first = idx.saturating_sub(snippets.context_lines);
+    let last = (idx + snippets.context_lines).min(lines.len() - 1);
+    let width = (last + 1).to_string().len();
+    writeln!(f, "{indent}{}", gutter(None, width, palette))?;
+    for (idx, text) in lines.iter().enumerate().take(last + 1).skip(first) {
+        let numbered = gutter(Some(idx + 1), width, palette);
+        match text.trim_end() {
+            "" => writeln!(f, "{indent}{numbered}")?,
+            text => writeln!(f, "{indent}{numbered} {text}")?,
+        }
+        if idx + 1 == line as usize {
+            let column = (column as usize).saturating_sub(1);
+            let end = text
+                .char_indices()
+                .nth(column)
+                .map_or(text.len(), |(idx, _)| idx);
+            let padding = padding(&text[..end]);
+            let caret = palette.paint(Color::Red, '^');
+            let gutter = gutter(None, width, palette);
+            writeln!(f, "{indent}{gutter} {padding}{caret}")?;
+        }
+    }
+    Ok(())
+}
+
+/// The gutter of a snippet line, e.g. ` 12 |`, or `    |` without a line
+/// `number`.
+pub(crate) fn gutter(
+    number: Option<usize>,
+    width: usize,
+    palette: &Palette,
+) -> String {
+    let gutter = match number {
+        Some(number) => format!("{number:>width$} |"),
+        None => format!("{:width$} |", ""),
+    };
+    palette.paint(Color::Blue, gutter)
+}
+
+/// Whitespace as wide as `text`. Tabs are kept, so that whatever follows
+/// the padding lines up with the character after `text`.
+pub(crate) fn padding(text: &str) -> String {
+    text.chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+/// Find the source file at `file`, which may be relative to the working
+/// directory, the package or the workspace root (e.g. `src/lib.rs` or
+/// `my-crate/src/lib.rs`). Directories above the workspace root aren't
+/// searched, so that a file of an unrelated crate isn't picked up.
+fn find_source_file(file: &str) -> Option<PathBuf> {
+    let file = Path::new(file);
+    if file.is_absolute() {
+        return file.is_file().then(|| file.to_path_buf());
+    }
+    let cwd = std::env::current_dir().ok();
+    let manifest_dir =
+        std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from);
+    let workspace_root = normalize::workspace_root().map(Path::to_path_buf);
+    [cwd, manifest_dir, workspace_root]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(file))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_source_files() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let snippet_rs = manifest_dir.join("src/snippet.rs");
+        assert_eq!(
+            find_source_file("src/snippet.rs"),
+            Some(snippet_rs.clone())
+        );
+        let workspace_root = normalize::workspace_root().unwrap();
+        let relative = snippet_rs.strip_prefix(workspace_root).unwrap();
+        assert!(find_source_file(relative.to_str().unwrap()).is_some());
+        // Only relative to the parent of the workspace root:
+        let outside =
+            Path::new(workspace_root.file_name().unwrap()).join(relative);
+        assert_eq!(find_source_file(outside.to_str().unwrap()), None);
+        assert_eq!(find_source_file("src/missing.rs"), None);
+    }
+}
@@ -0,0 +1,230 @@
+//! A pointer-sized alternative to [`ErrorCtx`].
+
+use crate::{CapturePolicy, CtxLocation, ErrorCtx, capture_policy};
+use std::backtrace::BacktraceStatus;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::panic::Location;
+use std::ptr::NonNull;
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+/// A pointer-sized alternative to [`ErrorCtx`], which keeps the errors that
+/// contain it (and `Result`s of those errors) small.
+///
+/// When no backtrace is captured, it holds only the `&'static Location` where
+/// it was created. Otherwise it holds a boxed `ErrorCtx`, so that the cost of
+/// the allocation is only paid when a backtrace is captured anyway. The same
+/// goes for recording a propagation trail (see [`ErrorCtx::trace`]), input
+/// spans (see [`ErrorCtx::add_span`]) and owned locations (see
+/// [`ErrorCtx::at`]).
+///
+/// This type is used for the context field of types annotated with
+/// `#[err_marks_the_spot(ctx_repr = "thin")]`.
+pub struct ThinErrorCtx {
+    /// A tagged pointer: if the `BOXED_TAG` bit is set, this points to a
+    /// `Box<ErrorCtx>`. Otherwise, it points to a `&'static Location`.
+    ptr: NonNull<()>,
+    _owns: PhantomData<Box<ErrorCtx>>,
+}
+
+const BOXED_TAG: usize = 1;
+
+// The tag is stored in the lowest bit of the pointer, which is always 0
+// for both kinds of pointers:
+const _: () = assert!(align_of::<Location<'static>>() > BOXED_TAG);
+const _: () = assert!(align_of::<ErrorCtx>() > BOXED_TAG);
+
+// SAFETY: A `ThinErrorCtx` is either a `&'static Location` or a
+//         `Box<ErrorCtx>`, and both of those are `Send` and `Sync`.
+unsafe impl Send for ThinErrorCtx {}
+unsafe impl Sync for ThinErrorCtx {}
+
+/// The decoded tagged pointer of a `ThinErrorCtx`.
+enum Repr<'a> {
+    Location(&'static Location<'static>),
+    Boxed(&'a ErrorCtx),
+}
+
+impl ThinErrorCtx {
+    /// Create a context for the caller's location, which captures a
+    /// backtrace according to the process-wide [`CapturePolicy`].
+    #[track_caller]
+    pub fn new() -> Self {
+        Self::with_capture_policy(capture_policy())
+    }
+
+    /// Create a context for the caller's location, which captures a
+    /// backtrace according to `policy`. A process-wide policy of
+    /// [`CapturePolicy::Off`] takes precedence over `policy`.
+    #[track_caller]
+    pub fn with_capture_policy(policy: CapturePolicy) -> Self {
+        Self::from(ErrorCtx::with_capture_policy(policy))
+    }
+
+    /// Create a context for the caller's location without a backtrace.
+    /// See [`ErrorCtx::placeholder`].
+    #[track_caller]
+    pub fn placeholder() -> Self {
+        Self::from_location(Location::caller())
+    }
+
+    fn from_location(location: &'static Location<'static>) -> Self {
+        let ptr = NonNull::from(location).cast::<()>();
+        Self {
+            ptr,
+            _owns: PhantomData,
+        }
+    }
+
+    fn from_box(ctx: Box<ErrorCtx>) -> Self {
+        let ptr = Box::into_raw(ctx).map_addr(|addr| addr | BOXED_TAG);
+        // SAFETY: The pointer of a `Box` is non-null,
+        //         and setting a bit keeps it that way.
+        let ptr = unsafe { NonNull::new_unchecked(ptr.cast::<()>()) };
+        Self {
+            ptr,
+            _owns: PhantomData,
+        }
+    }
+
+    fn boxed_ptr(&self) -> Option<*mut ErrorCtx> {
+        let ptr = self.ptr.as_ptr();
+        (ptr.addr() & BOXED_TAG != 0)
+            .then(|| ptr.map_addr(|addr| addr & !BOXED_TAG).cast::<ErrorCtx>())
+    }
+
+    fn repr(&self) -> Repr<'_> {
+        match self.boxed_ptr() {
+            // SAFETY: The pointer was created by `Box::into_raw()` in
+            //         `Self::from_box()`, and it is owned by `self`.
+            Some(ptr) => Repr::Boxed(unsafe { &*ptr }),
+            // SAFETY: The pointer was created from a `&'static Location`
+            //         in `Self::from_location()`.
+            None => Repr::Location(unsafe {
+                self.ptr.cast::<Location<'static>>().as_ref()
+            }),
+        }
+    }
+
+    /// The location where the error was created.
+    pub fn location(&self) -> &CtxLocation {
+        self.as_ctx().location()
+    }
+
+    /// Whether the backtrace was captured.
+    pub fn backtrace_status(&self) -> BacktraceStatus {
+        match self.repr() {
+            Repr::Location(_) => BacktraceStatus::Disabled,
+            Repr::Boxed(ctx) => ctx.backtrace_status(),
+        }
+    }
+
+    /// View this context as an `ErrorCtx`.
+    ///
+    /// NOTE: A context without a backtrace has no `ErrorCtx` to refer to,
+    ///       so one is created for its location the first time that this
+    ///       is called, and then shared by all contexts with that location.
+    pub fn as_ctx(&self) -> &ErrorCtx {
+        match self.repr() {
+            Repr::Location(location) => location_only_ctx(location),
+            Repr::Boxed(ctx) => ctx,
+        }
+    }
+
+    /// View this context as a mutable `ErrorCtx`.
+    /// A context without a backtrace is boxed to make this possible.
+    pub fn as_ctx_mut(&mut self) -> &mut ErrorCtx {
+        if let Repr::Location(location) = self.repr() {
+            *self = Self::from_box(Box::new(ErrorCtx::location_only(location)));
+        }
+        let ptr = self.boxed_ptr().expect("the ErrorCtx should be boxed");
+        // SAFETY: See `Self::repr()`. `self` is borrowed mutably,
+        //         so no other reference to the `ErrorCtx` exists.
+        unsafe { &mut *ptr }
+    }
+
+    /// Convert this context into an `ErrorCtx`.
+    pub fn into_ctx(self) -> ErrorCtx {
+        let ctx = match (self.boxed_ptr(), self.repr()) {
+            // SAFETY: See `Self::repr()`. The `ErrorCtx` is moved out of the
+            //         `Box`, after which `self` is forgotten rather than
+            //         dropped so that it isn't freed twice.
+            (Some(ptr), _) => *unsafe { Box::from_raw(ptr) },
+            (None, Repr::Location(location)) => {
+                ErrorCtx::location_only(location)
+            }
+            (None, Repr::Boxed(_)) => unreachable!("the ErrorCtx isn't boxed"),
+        };
+        std::mem::forget(self);
+        ctx
+    }
+}
+
+impl From<ErrorCtx> for ThinErrorCtx {
+    fn from(ctx: ErrorCtx) -> Self {
+        match ctx.location().as_static() {
+            Some(location)
+                if ctx.backtrace_status() != BacktraceStatus::Captured
+                    && ctx.trail().is_empty()
+                    && ctx.spans().is_empty() =>
+            {
+                Self::from_location(location)
+            }
+            _ => Self::from_box(Box::new(ctx)),
+        }
+    }
+}
+
+impl Default for ThinErrorCtx {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ThinErrorCtx {
+    fn drop(&mut self) {
+        if let Some(ptr) = self.boxed_ptr() {
+            // SAFETY: See `Self::repr()`.
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
+
+impl fmt::Debug for ThinErrorCtx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_ctx(), f)
+    }
+}
+
+impl fmt::Display for ThinErrorCtx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_ctx(), f)
+    }
+}
+
+/// The `ErrorCtx`s without a backtrace for each location that was needed by
+/// `ThinErrorCtx::as_ctx()`. The number of locations in a program is finite,
+/// so these are leaked.
+static LOCATION_ONLY_CTXS: LazyLock<
+    RwLock<HashMap<&'static Location<'static>, &'static ErrorCtx>>,
+> = LazyLock::new(Default::default);
+
+fn location_only_ctx(
+    location: &'static Location<'static>,
+) -> &'static ErrorCtx {
+    let ctxs = LOCATION_ONLY_CTXS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(ctx) = ctxs.get(location) {
+        return ctx;
+    }
+    drop(ctxs);
+    let mut ctxs = LOCATION_ONLY_CTXS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    ctxs.entry(location).or_insert_with(|| {
+        Box::leak(Box::new(ErrorCtx::location_only(location)))
+    })
+}
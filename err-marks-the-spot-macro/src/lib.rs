@@ -1,131 +1,192 @@
-//!
+//! The `#[err_marks_the_spot]` attribute macro.
 #![allow(non_snake_case)]
 
 use proc_macro::{Delimiter, TokenStream, TokenTree, token_stream::IntoIter};
+use proc_macro_crate::FoundCrate;
 use proc_macro2::{
     Ident as Ident2, Punct as Punct2, Spacing as Spacing2, Span as Span2,
     TokenStream as TokenStream2, TokenTree as TokenTree2,
 };
-use quote::quote;
+use quote::{ToTokens, quote};
 use regex::{Match, Regex};
 use std::collections::HashMap;
 use std::iter::Peekable;
 use std::ops::Range;
 use std::sync::LazyLock;
+use syn::ext::IdentExt;
+use syn::parse::Parser;
 use syn::{
     AttrStyle, Attribute, Data, DataEnum, DataStruct, DeriveInput, Expr,
-    ExprLit, Field, FieldMutability, Fields, FieldsNamed, FieldsUnnamed, Lit,
-    LitInt, LitStr, MacroDelimiter, Meta, MetaList, Path, PathArguments,
-    PathSegment, Type, TypePath, Variant, Visibility, parse_macro_input,
+    ExprLit, Field, FieldMutability, Fields, FieldsNamed, FieldsUnnamed,
+    Generics, Lit, LitInt, LitStr, MacroDelimiter, Meta, MetaList, Path,
+    PathArguments, PathSegment, Type, TypePath, Variant, Visibility,
+    parse_macro_input, parse_quote,
     token::{Brace, Bracket, Colon, Paren, Pound, Pub},
 };
 
 #[proc_macro_attribute]
 pub fn err_marks_the_spot(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let item2 = item.clone();
-    let type_item @ DeriveInput {
+    let type_item = parse_macro_input!(item as DeriveInput);
+    match expand(attr, &type_item) {
+        Ok(stream) => TokenStream::from(stream),
+        Err(error) => TokenStream::from(error.to_compile_error()),
+    }
+}
+
+fn expand(attr: TokenStream, type_item: &DeriveInput) -> syn::Result<TokenStream2> {
+    let DeriveInput {
         attrs: item_attrs,
         vis: item_vis,
         ident: item_ident,
         generics: item_generics,
         data: item_data,
-    } = &parse_macro_input!(item2 as DeriveInput);
+    } = type_item;
+    if let Data::Union(u) = item_data {
+        return Err(syn::Error::new(
+            u.union_token.span,
+            "Unions are not supported",
+        ));
+    }
 
-    let type_attr_args = TypeAttrArgs::parse(attr);
-    let field_attrs = type_attr_args.field_attr_vec();
+    // Each stage is run even if an earlier one failed,
+    // so that as many errors as possible are reported at once:
+    let mut errors = Errors::default();
+    let type_attr_args = errors.check(TypeAttrArgs::parse(attr))
+        .unwrap_or_default();
     let ctor_attrs = type_attr_args.ctor_attr_vec();
+    let ctx_field = CtxField {
+        crate_path: type_attr_args.crate_path(),
+        name: type_attr_args.ctx_field_name(),
+        vis: errors.check(type_attr_args.ctx_field_vis(item_data))
+            .unwrap_or(Visibility::Inherited),
+        attrs: type_attr_args.field_attr_vec(),
+        zero_sized_attrs: errors.check(type_attr_args.zero_sized_field_attr_vec())
+            .flatten(),
+        capture_policy: type_attr_args.capture_policy(),
+        thin: type_attr_args.thin_ctx(),
+        serde: type_attr_args.serde_ctx(),
+    };
+    let CtxField { crate_path, name: ctx_name, .. } = &ctx_field;
+    errors.check(check_ctx_field_collisions(ctx_name, item_data));
     let impl_ctors_for_type = generate_ctor_impl_block(
+        &ctx_field,
         &ctor_attrs,
-        &item_ident,
-        &item_data,
-        &field_attrs,
+        item_ident,
+        item_generics,
+        item_data,
     );
 
+    let augmented_data = match &item_data {
+        Data::Union(_) => unreachable!("Unions are rejected up front"),
+        Data::Enum(e) => augment_enum(&ctx_field, e).map(Data::Enum),
+        Data::Struct(s) => Ok(Data::Struct(augment_struct(&ctx_field, s))),
+    };
+    let augmented_data = errors.check(augmented_data);
+
+    let impl_Display_for_type = errors.check(gen_impl_Display_for_type(
+        crate_path,
+        &type_attr_args.render_style(crate_path),
+        ctx_name,
+        type_item,
+    ));
+    let impl_Error_for_type = errors.check(gen_impl_Error_for_type(type_item));
+    let impl_From_for_type = errors.check(gen_impl_From_for_type(type_item));
+    let impl_HasErrorCtx_for_type = gen_impl_HasErrorCtx_for_type(
+        &ctx_field,
+        type_attr_args.build_feature.as_ref(),
+        type_item,
+    );
+    errors.finish()?;
+
     let augmented_type_item = DeriveInput {
         attrs: item_attrs.clone(),
         vis: item_vis.clone(),
         ident: item_ident.clone(),
         generics: item_generics.clone(),
-        data: match &item_data {
-            Data::Union(_) => panic!("Unions are not supported"),
-            Data::Enum(e) => Data::Enum(augment_enum(
-                type_attr_args.build_feature.as_ref(),
-                &e,
-                &field_attrs,
-            )),
-            Data::Struct(s) => Data::Struct(augment_struct(
-                type_attr_args.build_feature.as_ref(),
-                &s,
-                &field_attrs,
-            )),
-        },
+        data: augmented_data.unwrap(),
     };
-
-    let impl_Display_for_type: TokenStream2 = gen_impl_Display_for_type(
-        type_attr_args.build_feature.as_ref(),
-        &type_item,
-    );
-
-    TokenStream::from(quote! {
+    Ok(quote! {
         #augmented_type_item
         #impl_ctors_for_type
         #impl_Display_for_type
+        #impl_Error_for_type
+        #impl_From_for_type
+        #impl_HasErrorCtx_for_type
     })
 }
 
+/// An accumulator for `syn::Error`s, which allows reporting multiple errors
+/// in a single macro expansion rather than only the first one.
+#[derive(Default)]
+struct Errors(Option<syn::Error>);
+
+impl Errors {
+    fn push(&mut self, error: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    fn check<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
+
+    fn finish(self) -> syn::Result<()> {
+        match self.0 {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
+
+    /// Collect all `Ok` values, or all of the errors if there are any.
+    fn collect<T>(
+        results: impl IntoIterator<Item = syn::Result<T>>,
+    ) -> syn::Result<Vec<T>> {
+        let mut errors = Self::default();
+        let values = results.into_iter()
+            .filter_map(|result| errors.check(result))
+            .collect();
+        errors.finish()?;
+        Ok(values)
+    }
+}
+
 #[rustfmt::skip]
-fn augment_enum(
-    build_feature: Option<&BuildFeatureAttr>,
-    e: &DataEnum,
-    field_attrs: &[Attribute],
-) -> DataEnum {
+fn augment_enum(ctx_field: &CtxField, e: &DataEnum) -> syn::Result<DataEnum> {
+    let mut errors = Errors::default();
+    for Variant { discriminant, .. } in &e.variants {
+        if let Some((_eq, expr)) = discriminant {
+            errors.push(syn::Error::new_spanned(
+                expr,
+                "Enum variant discriminants are not supported",
+            ));
+        }
+    }
+    errors.finish()?;
+    // Enum variant fields cannot have a visibility of their own:
+    let field_vis = Visibility::Inherited;
     let output_variants = e.variants.iter()
         .map(|Variant { attrs, ident, fields, discriminant }| {
-            if discriminant.is_some() {
-                panic!("Enum variant discriminants are not supported");
-            }
-            let field_name = Some(Ident2::new("ctx", Span2::call_site()));
-            let field_vis = Visibility::Inherited;
             let output_fields = match fields {
                 Fields::Named(n) => Fields::Named(FieldsNamed {
                     brace_token: n.brace_token,
                     named: std::iter::empty()
-                        .chain(n.named.iter().cloned())
-                        .chain(vec![
-                            ctx_field(
-                                build_feature,
-                                field_attrs,
-                                field_vis,
-                                field_name
-                            )
-                        ])
+                        .chain(n.named.iter().map(strip_helper_attrs))
+                        .chain(ctx_field.fields(&field_vis, true))
                         .collect(),
                 }),
                 Fields::Unit => Fields::Named(FieldsNamed {
                     brace_token: Brace(Span2::call_site()),
                     named: std::iter::empty()
-                        .chain(vec![
-                            ctx_field(
-                                build_feature,
-                                field_attrs,
-                                field_vis,
-                                field_name
-                            )
-                        ])
+                        .chain(ctx_field.fields(&field_vis, true))
                         .collect(),
                 }),
                 Fields::Unnamed(u) => Fields::Unnamed(FieldsUnnamed {
                     paren_token: u.paren_token,
                     unnamed: std::iter::empty()
-                        .chain(u.unnamed.iter().cloned()) // user-defined fields
-                        .chain(vec![
-                            ctx_field(
-                                build_feature,
-                                field_attrs,
-                                field_vis,
-                                None
-                            )
-                        ])
+                        .chain(u.unnamed.iter().map(strip_helper_attrs)) // user-defined fields
+                        .chain(ctx_field.fields(&field_vis, false))
                         .collect(),
                 })
             };
@@ -137,36 +198,24 @@ fn augment_enum(
             }
         })
         .collect();
-    DataEnum {
+    Ok(DataEnum {
         enum_token: e.enum_token,
         brace_token: e.brace_token,
         variants: output_variants,
-    }
+    })
 }
 
 #[rustfmt::skip]
-fn augment_struct(
-    build_feature: Option<&BuildFeatureAttr>,
-    s: &DataStruct,
-    field_attrs: &[Attribute],
-) -> DataStruct {
-    let field_vis = Visibility::Public(Pub(Span2::call_site()));
-    let field_name = Some(Ident2::new("ctx", Span2::call_site()));
+fn augment_struct(ctx_field: &CtxField, s: &DataStruct) -> DataStruct {
+    let field_vis = &ctx_field.vis;
     match &s.fields {
         Fields::Named(n) => DataStruct {
             struct_token: s.struct_token,
             fields: Fields::Named(FieldsNamed {
                 brace_token: Brace(Span2::call_site()),
                 named: std::iter::empty()
-                    .chain(n.named.iter().cloned()) // user-defined fields
-                    .chain([
-                        ctx_field(
-                            build_feature,
-                            field_attrs,
-                            field_vis,
-                            field_name
-                        ),
-                    ])
+                    .chain(n.named.iter().map(strip_helper_attrs)) // user-defined fields
+                    .chain(ctx_field.fields(field_vis, true))
                     .collect(),
             }),
             semi_token: s.semi_token,
@@ -176,14 +225,7 @@ fn augment_struct(
             fields: Fields::Named(FieldsNamed {
                 brace_token: Brace(Span2::call_site()),
                 named: std::iter::empty()
-                    .chain([
-                        ctx_field(
-                            build_feature,
-                            field_attrs,
-                            field_vis,
-                            field_name
-                        ),
-                    ])
+                    .chain(ctx_field.fields(field_vis, true))
                     .collect(),
             }),
             semi_token: s.semi_token,
@@ -193,15 +235,8 @@ fn augment_struct(
             fields: Fields::Unnamed(FieldsUnnamed {
                 paren_token: Paren(Span2::call_site()),
                 unnamed: std::iter::empty()
-                    .chain(u.unnamed.iter().cloned()) // user-defined fields
-                    .chain([
-                        ctx_field(
-                            build_feature,
-                            field_attrs,
-                            field_vis,
-                            None
-                        ),
-                    ])
+                    .chain(u.unnamed.iter().map(strip_helper_attrs)) // user-defined fields
+                    .chain(ctx_field.fields(field_vis, false))
                     .collect(),
             }),
             semi_token: s.semi_token,
@@ -209,64 +244,176 @@ fn augment_struct(
     }
 }
 
-// pub ctx: error_context_core::ErrorCtx
-fn ctx_field(
-    build_feature: Option<&BuildFeatureAttr>,
-    field_attrs: &[Attribute],
-    vis: Visibility,
-    field_name: Option<Ident2>,
-) -> Field {
+/// The names of the field-level helper attributes that are understood by
+/// `#[err_marks_the_spot]`.  They are removed from the emitted type.
+const FIELD_HELPER_ATTRS: &[&str] = &["source", "from"];
+
+fn is_helper_attr(attr: &Attribute, name: &str) -> bool {
+    attr.path().is_ident(name)
+}
+
+fn strip_helper_attrs(field: &Field) -> Field {
     Field {
-        attrs: field_attrs.to_vec().into_iter()
-            .chain(if let Some(feature) = build_feature {
-                vec![ feature.to_attr() ]
-            } else {
-                vec![/* Don't add #[cfg(feature = <FEATURE>)] attribure */]
+        attrs: field.attrs.iter()
+            .filter(|attr| {
+                !FIELD_HELPER_ATTRS.iter().any(|name| is_helper_attr(attr, name))
             })
+            .cloned()
             .collect(),
-        vis,
-        mutability: FieldMutability::None,
-        ident: field_name,
-        colon_token: Some(Colon(Span2::call_site())),
-        ty: Type::Path(TypePath {
-            qself: None,
-            path: Path {
-                leading_colon: None,
-                segments: vec![
-                    PathSegment {
-                        ident: Ident2::new("err_marks_the_spot", Span2::call_site()),
-                        arguments: PathArguments::None,
-                    },
-                    PathSegment {
-                        ident: Ident2::new("ErrorCtx", Span2::call_site()),
+        ..field.clone()
+    }
+}
+
+/// Report an error for each user-defined field that has the same name as the
+/// `ErrorCtx` field that is added to the type.
+fn check_ctx_field_collisions(
+    ctx_name: &Ident2,
+    item_data: &Data,
+) -> syn::Result<()> {
+    let fields: Vec<&Fields> = match item_data {
+        Data::Union(_) => unreachable!("Unions are rejected up front"),
+        Data::Struct(s) => vec![&s.fields],
+        Data::Enum(e) => e.variants.iter().map(|v| &v.fields).collect(),
+    };
+    let mut errors = Errors::default();
+    for ident in fields.iter().flat_map(|f| f.iter()).flat_map(|f| &f.ident) {
+        if ident == ctx_name {
+            errors.push(syn::Error::new(ident.span(), format!(
+                "Field '{ident}' collides with the ErrorCtx field, \
+                 which can be renamed with the ctx_field attribute argument"
+            )));
+        }
+    }
+    errors.finish()
+}
+
+/// Describes the `ErrorCtx` field that is added to an annotated type.
+struct CtxField {
+    /// The path to the `err_marks_the_spot` crate, or to a re-export of it.
+    crate_path: Path,
+    name: Ident2,
+    /// The visibility of the field on structs.
+    vis: Visibility,
+    /// The attributes of the field, which are also added to its initializers.
+    attrs: Vec<Attribute>,
+    /// If the field is replaced by a `NoErrorCtx` field when the build
+    /// feature is disabled, the attributes of that replacement field.
+    zero_sized_attrs: Option<Vec<Attribute>>,
+    /// The `CapturePolicy` variant that the field is initialized with, if any.
+    capture_policy: Option<Ident2>,
+    /// Whether the type of the field is `ThinErrorCtx` rather than `ErrorCtx`.
+    thin: bool,
+    /// How the field is (de)serialized by serde derives, if at all.
+    serde: Option<SerdeCtx>,
+}
+
+impl CtxField {
+    /// The name of the type of the field in the `err_marks_the_spot` crate.
+    fn type_name(&self) -> &'static str {
+        if self.thin { "ThinErrorCtx" } else { "ErrorCtx" }
+    }
+
+    /// The `#[serde(...)]` attributes of the fields returned by
+    /// `Self::fields()`. These are not added to the initializers.
+    fn serde_attrs(&self) -> (Vec<Attribute>, Vec<Attribute>) {
+        let Some(serde) = &self.serde else {
+            return (vec![], vec![]);
+        };
+        let crate_path = &self.crate_path;
+        let type_name = Ident2::new(self.type_name(), Span2::call_site());
+        let placeholder = quote! { #crate_path::#type_name::placeholder }
+            .to_string();
+        let attr = match serde {
+            SerdeCtx::Skip => parse_quote! {
+                #[serde(skip, default = #placeholder)]
+            },
+            SerdeCtx::Ctx => parse_quote! {
+                #[serde(default = #placeholder)]
+            },
+        };
+        (vec![attr], vec![parse_quote! { #[serde(skip)] }])
+    }
+
+    // pub ctx: ::err_marks_the_spot::ErrorCtx
+    fn fields(&self, vis: &Visibility, named: bool) -> Vec<Field> {
+        let (serde_attrs, zero_sized_serde_attrs) = self.serde_attrs();
+        let field = |attrs: &[Attribute], serde_attrs: &[Attribute], type_name: &str| Field {
+            attrs: attrs.iter().chain(serde_attrs).cloned().collect(),
+            vis: vis.clone(),
+            mutability: FieldMutability::None,
+            ident: named.then(|| self.name.clone()),
+            colon_token: named.then(|| Colon(Span2::call_site())),
+            ty: Type::Path(TypePath {
+                qself: None,
+                path: {
+                    let mut path = self.crate_path.clone();
+                    path.segments.push(PathSegment {
+                        ident: Ident2::new(type_name, Span2::call_site()),
                         arguments: PathArguments::None,
-                    },
-                ].into_iter().collect()
+                    });
+                    path
+                },
+            }),
+        };
+        std::iter::empty()
+            .chain([field(&self.attrs, &serde_attrs, self.type_name())])
+            .chain(self.zero_sized_attrs.as_ref()
+                .map(|attrs| field(attrs, &zero_sized_serde_attrs, "NoErrorCtx")))
+            .collect()
+    }
+
+    /// The initializers of the fields returned by `Self::fields()`, for use in
+    /// struct expressions. Each initializer is followed by a comma.
+    fn initializers(&self, named: bool) -> TokenStream2 {
+        let Self {
+            crate_path, name, attrs, zero_sized_attrs, capture_policy, ..
+        } = self;
+        let name = named.then(|| quote! { #name: });
+        let type_name = Ident2::new(self.type_name(), Span2::call_site());
+        let ctx = match capture_policy {
+            Some(policy) => quote! {
+                #crate_path::#type_name::with_capture_policy(
+                    #crate_path::CapturePolicy::#policy
+                )
             },
-        }),
+            None => quote! { #crate_path::#type_name::new() },
+        };
+        let zero_sized = zero_sized_attrs.as_ref().map(|zero_sized_attrs| {
+            quote! {
+                #(#zero_sized_attrs)*
+                #name #crate_path::NoErrorCtx::new(),
+            }
+        });
+        quote! {
+            #(#attrs)*
+            #name #ctx,
+            #zero_sized
+        }
     }
 }
 
 fn generate_ctor_impl_block(
+    ctx_field: &CtxField,
     ctor_attrs: &[Attribute],
     type_name: &Ident2,
+    generics: &Generics,
     item_data: &Data,
-    field_attrs: &[Attribute],
 ) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     match &item_data {
-        Data::Union(_) => panic!("Unions are not supported"),
+        Data::Union(_) => unreachable!("Unions are rejected up front"),
         Data::Enum(e) => {
-            let enum_ctors = generate_enum_ctors(&e, field_attrs, ctor_attrs);
+            let enum_ctors = generate_enum_ctors(ctx_field, e, ctor_attrs);
             quote! {
-                impl #type_name {
+                impl #impl_generics #type_name #ty_generics #where_clause {
                     #( #enum_ctors )*
                 }
             }
         },
         Data::Struct(s) => {
-            let struct_ctor = generate_struct_ctor(&s, field_attrs, ctor_attrs);
+            let struct_ctor = generate_struct_ctor(ctx_field, s, ctor_attrs);
             quote! {
-                impl #type_name {
+                impl #impl_generics #type_name #ty_generics #where_clause {
                     #struct_ctor
                 }
             }
@@ -275,8 +422,8 @@ fn generate_ctor_impl_block(
 }
 
 fn generate_struct_ctor(
+    ctx_field: &CtxField,
     s: &DataStruct,
-    field_attrs: &[Attribute],
     ctor_attrs: &[Attribute],
 ) -> TokenStream2 {
     match &s.fields {
@@ -284,37 +431,27 @@ fn generate_struct_ctor(
             let params = n.named.iter()
                 .map(|Field { ident, ty, .. }| quote! { #ident : impl Into<#ty> });
             let field_initializers = n.named.iter()
-                .map(|Field { ident, ty: _, .. }| quote! { #ident: #ident.into() })
-                .chain([
-                    quote! {
-                        #(#field_attrs)*
-                        ctx: err_marks_the_spot::ErrorCtx::new(),
-                    },
-                ]);
+                .map(|Field { ident, ty: _, .. }| quote! { #ident: #ident.into(), })
+                .chain([ctx_field.initializers(true)]);
             quote! {
                 #(#ctor_attrs)*
                 #[track_caller]
                 pub fn new( #(#params),* ) -> Self {
                     Self {
-                        #(#field_initializers),*
+                        #(#field_initializers)*
                     }
                 }
             }
         },
         Fields::Unit => {
             let field_initializers = std::iter::empty()
-                .chain([
-                    quote! {
-                        #(#field_attrs)*
-                        ctx: err_marks_the_spot::ErrorCtx::new(),
-                    },
-                ]);
+                .chain([ctx_field.initializers(true)]);
             quote! {
                 #(#ctor_attrs)*
                 #[track_caller]
                 pub fn new() -> Self {
                     Self {
-                        #(#field_initializers),*
+                        #(#field_initializers)*
                     }
                 }
             }
@@ -330,20 +467,15 @@ fn generate_struct_ctor(
                 .map(|(i, Field { ident: _, ty: _, .. })| {
                     let ident = format!("field{i}");
                     let ident = Ident2::new(&ident, Span2::call_site());
-                    quote! { #ident.into() }
+                    quote! { #ident.into(), }
                 })
-                .chain([
-                    quote! {
-                        #(#field_attrs)*
-                        err_marks_the_spot::ErrorCtx::new(),
-                    },
-                ]);
+                .chain([ctx_field.initializers(false)]);
             quote! {
                 #(#ctor_attrs)*
                 #[track_caller]
                 pub fn new( #(#params),* ) -> Self {
                     Self(
-                        #(#field_initializers),*
+                        #(#field_initializers)*
                     )
                 }
             }
@@ -352,8 +484,8 @@ fn generate_struct_ctor(
 }
 
 fn generate_enum_ctors(
+    ctx_field: &CtxField,
     e: &DataEnum,
-    field_attrs: &[Attribute],
     ctor_attrs: &[Attribute],
 ) -> Vec<TokenStream2> {
     e.variants.iter()
@@ -369,38 +501,28 @@ fn generate_enum_ctors(
                         });
                     let field_initializers = n.named.iter()
                         .map(|Field { ident, ty: _, .. }| {
-                            quote! { #ident: #ident.into() }
+                            quote! { #ident: #ident.into(), }
                         })
-                        .chain([
-                            quote! {
-                                #(#field_attrs)*
-                                ctx: err_marks_the_spot::ErrorCtx::new(),
-                            },
-                        ]);
+                        .chain([ctx_field.initializers(true)]);
                     quote! {
                         #(#ctor_attrs)*
                         #[track_caller]
                         pub fn #ctor_name( #(#params),* ) -> Self {
                             Self::#variant_name {
-                                #(#field_initializers),*
+                                #(#field_initializers)*
                             }
                         }
                     }
                 },
                 Fields::Unit => {
                     let field_initializers = std::iter::empty()
-                        .chain([
-                            quote! {
-                                #(#field_attrs)*
-                                ctx: err_marks_the_spot::ErrorCtx::new(),
-                            },
-                        ]);
+                        .chain([ctx_field.initializers(true)]);
                     quote! {
                         #(#ctor_attrs)*
                         #[track_caller]
                         pub fn #ctor_name() -> Self {
                             Self::#variant_name {
-                                #(#field_initializers),*
+                                #(#field_initializers)*
                             }
                         }
                     }
@@ -416,20 +538,15 @@ fn generate_enum_ctors(
                         .map(|(i, Field { ident: _, ty: _, .. })| {
                             let ident = format!("field{i}");
                             let ident = Ident2::new(&ident, Span2::call_site());
-                            quote! { #ident.into() }
+                            quote! { #ident.into(), }
                         })
-                        .chain([
-                            quote! {
-                                #(#field_attrs)*
-                                err_marks_the_spot::ErrorCtx::new()
-                            },
-                        ]);
+                        .chain([ctx_field.initializers(false)]);
                     quote! {
                         #(#ctor_attrs)*
                         #[track_caller]
                         pub fn #ctor_name( #(#params),* ) -> Self {
                             Self::#variant_name(
-                                #(#field_initializers),*
+                                #(#field_initializers)*
                             )
                         }
                     }
@@ -439,43 +556,93 @@ fn generate_enum_ctors(
         .collect()
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct TypeAttrArgs {
     build_feature: Option<BuildFeatureAttr>,
     inline_ctors: Option<InlineCtorsAttr>,
+    crate_path: Option<CratePathAttr>,
+    ctx_field: Option<CtxFieldAttr>,
+    ctx_vis: Option<CtxVisAttr>,
+    disabled_ctx: Option<DisabledCtxAttr>,
+    render: Option<RenderAttr>,
+    backtrace: Option<BacktraceAttr>,
+    ctx_repr: Option<CtxReprAttr>,
+    serde: Option<SerdeAttr>,
 }
 
 impl TypeAttrArgs {
-    fn parse(attr: TokenStream) -> Self {
+    /// The names of all recognized attribute arguments.
+    const NAMES: &[&str] = &[
+        "feature", "inline_ctors", "crate", "ctx_field", "ctx_vis",
+        "disabled_ctx", "render", "backtrace", "ctx_repr", "serde",
+    ];
+
+    fn parse(attr: TokenStream) -> syn::Result<Self> {
         let mut attr_iter = attr.into_iter().peekable();
-        let mut field_attrs = Self {
-            build_feature: None,
-            inline_ctors: None,
-        };
-        let mut loop_count = 0;
+        let mut type_attr_args = Self::default();
+        let mut errors = Errors::default();
         while let Some(tt) = attr_iter.peek() {
+            let span = Span2::from(tt.span());
             let peeked = tt.to_string();
-            // TODO
-            // if loop_count > 0 {
-            //     attr_arg::parse_comma_token(&mut attr_iter);
-            // }
-            match &*peeked {
-                "," if loop_count > 0 => {
-                    attr_arg::parse_comma_token(&mut attr_iter);
-                }
-                "feature" => {
-                    let arg = BuildFeatureAttr::parse(&mut attr_iter);
-                    field_attrs.build_feature = Some(arg);
-                },
-                "inline_ctors" => {
-                    let arg = InlineCtorsAttr::parse(&mut attr_iter);
-                    field_attrs.inline_ctors = Some(arg);
-                },
-                _ => panic!("Expected attr name 'feature', 'inline_ctors', got {peeked}")
+            let parsed = match &*peeked {
+                "feature" => BuildFeatureAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.build_feature, arg, span)
+                    }),
+                "inline_ctors" => InlineCtorsAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.inline_ctors, arg, span)
+                    }),
+                "crate" => CratePathAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.crate_path, arg, span)
+                    }),
+                "ctx_field" => CtxFieldAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.ctx_field, arg, span)
+                    }),
+                "ctx_vis" => CtxVisAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.ctx_vis, arg, span)
+                    }),
+                "disabled_ctx" => DisabledCtxAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.disabled_ctx, arg, span)
+                    }),
+                "render" => RenderAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.render, arg, span)
+                    }),
+                "backtrace" => BacktraceAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.backtrace, arg, span)
+                    }),
+                "ctx_repr" => CtxReprAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.ctx_repr, arg, span)
+                    }),
+                "serde" => SerdeAttr::parse(&mut attr_iter)
+                    .and_then(|arg| {
+                        attr_arg::set_once(&mut type_attr_args.serde, arg, span)
+                    }),
+                _ => Err(syn::Error::new(span, format!(
+                    "Expected one of the attribute arguments {}, got '{peeked}'",
+                    Self::NAMES.iter()
+                        .map(|name| format!("'{name}'"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ))),
+            };
+            let parsed = parsed
+                .and_then(|()| attr_arg::parse_comma_token(&mut attr_iter));
+            if let Err(error) = parsed {
+                errors.push(error);
+                // Recover by skipping to the next attribute argument:
+                attr_arg::skip_past_comma(&mut attr_iter);
             }
-            loop_count += 1;
         }
-        field_attrs
+        errors.finish()?;
+        Ok(type_attr_args)
     }
 
     fn field_attr_vec(&self) -> Vec<Attribute> {
@@ -486,6 +653,104 @@ impl TypeAttrArgs {
         vec
     }
 
+    /// The path to the `err_marks_the_spot` crate, or to a re-export of it.
+    /// By default this is the name under which the consumer crate depends on
+    /// `err-marks-the-spot` (which may be renamed), as an absolute path that
+    /// cannot be shadowed by items in the consumer crate.  If the consumer
+    /// crate doesn't depend on it directly, this falls back to
+    /// `::err_marks_the_spot`, and a re-export needs the `crate` argument.
+    fn crate_path(&self) -> Path {
+        if let Some(CratePathAttr { value, .. }) = &self.crate_path {
+            return value.clone();
+        }
+        match proc_macro_crate::crate_name("err-marks-the-spot") {
+            Ok(FoundCrate::Name(name)) => {
+                let name = Ident2::new(&name, Span2::call_site());
+                parse_quote! { ::#name }
+            }
+            // NOTE: `Itself` means that the macro is expanded in the façade
+            //       crate's own doctests or examples, which are separate
+            //       crates that refer to the façade by its name.
+            Ok(FoundCrate::Itself) | Err(_) => {
+                parse_quote! { ::err_marks_the_spot }
+            }
+        }
+    }
+
+    /// The name of the `ErrorCtx` field, which is `ctx` by default.
+    fn ctx_field_name(&self) -> Ident2 {
+        match &self.ctx_field {
+            Some(CtxFieldAttr { value, .. }) => value.clone(),
+            None => Ident2::new("ctx", Span2::call_site()),
+        }
+    }
+
+    /// The visibility of the `ErrorCtx` field, which is `pub` by default.
+    /// The fields of enum variants cannot have a visibility of their own.
+    fn ctx_field_vis(&self, item_data: &Data) -> syn::Result<Visibility> {
+        match (&self.ctx_vis, item_data) {
+            (Some(CtxVisAttr { name, .. }), Data::Enum(_)) => {
+                Err(syn::Error::new(name.span(), format!(
+                    "Attribute argument {name} is not supported on enums"
+                )))
+            },
+            (Some(CtxVisAttr { value, .. }), _) => Ok(value.clone()),
+            (None, _) => Ok(Visibility::Public(Pub(Span2::call_site()))),
+        }
+    }
+
+    /// If the `ErrorCtx` field is replaced by a zero-sized `NoErrorCtx` field
+    /// when the build feature is disabled, the attributes of that field.
+    fn zero_sized_field_attr_vec(&self) -> syn::Result<Option<Vec<Attribute>>> {
+        match (&self.disabled_ctx, &self.build_feature) {
+            (Some(DisabledCtxAttr { value: DisabledCtx::ZeroSized, .. }), Some(build_feature)) => {
+                Ok(Some(vec![build_feature.to_not_attr()]))
+            },
+            (Some(DisabledCtxAttr { name, .. }), None) => {
+                Err(syn::Error::new(name.span(), format!(
+                    "Attribute argument {name} requires the feature attribute argument"
+                )))
+            },
+            (Some(DisabledCtxAttr { value: DisabledCtx::Removed, .. }), _) | (None, _) => {
+                Ok(None)
+            },
+        }
+    }
+
+    /// An `Option<RenderStyle>` expression for the render style of the type.
+    /// Without the render attribute argument, this is `None` so that the
+    /// process-wide render style is used.
+    fn render_style(&self, crate_path: &Path) -> TokenStream2 {
+        match &self.render {
+            Some(RenderAttr { value, .. }) => {
+                let variant = Ident2::new(value.variant_name(), Span2::call_site());
+                quote! { Some(#crate_path::RenderStyle::#variant) }
+            },
+            None => quote! { None },
+        }
+    }
+
+    /// The `CapturePolicy` variant that the `ErrorCtx` field is initialized
+    /// with. Without the backtrace attribute argument, this is `None` so that
+    /// the process-wide capture policy is used.
+    fn capture_policy(&self) -> Option<Ident2> {
+        self.backtrace.as_ref().map(|BacktraceAttr { value, .. }| {
+            Ident2::new(value.variant_name(), Span2::call_site())
+        })
+    }
+
+    /// Whether the type of the context field is `ThinErrorCtx`,
+    /// which is `false` by default.
+    fn thin_ctx(&self) -> bool {
+        matches!(self.ctx_repr, Some(CtxReprAttr { value: CtxRepr::Thin, .. }))
+    }
+
+    /// How the context field is (de)serialized. Without the serde attribute
+    /// argument, the field gets no serde attributes.
+    fn serde_ctx(&self) -> Option<SerdeCtx> {
+        self.serde.as_ref().map(|SerdeAttr { value, .. }| *value)
+    }
+
     fn ctor_attr_vec(&self) -> Vec<Attribute> {
         let mut vec = vec![];
         if let Some(inline_ctors) = &self.inline_ctors {
@@ -506,12 +771,18 @@ struct BuildFeatureAttr {
 }
 
 impl BuildFeatureAttr {
-    fn parse(attr_iter: &mut Peekable<IntoIter>) -> Self {
+    /// Return the `#[cfg(not(feature = <BUILD_FEATURE_NAME>))]` attribute.
+    fn to_not_attr(&self) -> Attribute {
+        let feature = &self.value;
+        parse_quote! { #[cfg(not(feature = #feature))] }
+    }
+
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
         let attr_arg_name = "feature";
-        let (name, name_stream) = attr_arg::parse_name(attr_iter, attr_arg_name);
-        attr_arg::parse_eq_token(attr_iter, &attr_arg_name);
-        let value: Expr = attr_arg::parse_value_expr(attr_iter, attr_arg_name);
-        Self { name, name_stream, value }
+        let (name, name_stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let value: Expr = attr_arg::parse_value_expr(attr_iter, &name)?;
+        Ok(Self { name, name_stream, value })
     }
 
     fn to_attr(&self) -> Attribute {
@@ -563,11 +834,18 @@ struct InlineCtorsAttr {
 }
 
 impl InlineCtorsAttr {
-    fn parse(attr_iter: &mut Peekable<IntoIter>) -> Self {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
         let attr_arg_name = "inline_ctors";
-        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name);
-        let value = attr_arg::parse_parenthesized_value_ident(attr_iter);
-        Self { name, value }
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        let value = attr_arg::parse_parenthesized_value_ident(attr_iter)?;
+        if let Some(value) = &value {
+            if value != "always" && value != "never" {
+                return Err(syn::Error::new(value.span(), format!(
+                    "Expected 'always' or 'never', got '{value}'"
+                )));
+            }
+        }
+        Ok(Self { name, value })
     }
 
     fn to_attr(&self) -> Attribute {
@@ -604,142 +882,800 @@ impl InlineCtorsAttr {
     }
 }
 
+// Currently ONLY recognizes the attribute arguments:
+// - crate = "<PATH>"
+#[derive(Debug)]
+struct CratePathAttr {
+    #[allow(unused)]
+    name: Ident2,
+    value: Path,
+}
+
+impl CratePathAttr {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
+        let attr_arg_name = "crate";
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let lit = attr_arg::parse_value_lit_str(attr_iter, &name)?;
+        let value = lit.parse::<Path>().map_err(|error| syn::Error::new(
+            lit.span(),
+            format!("Expected a path in attribute argument {name}: {error}"),
+        ))?;
+        Ok(Self { name, value })
+    }
+}
+
+// Currently ONLY recognizes the attribute arguments:
+// - ctx_field = "<FIELD_NAME>"
+#[derive(Debug)]
+struct CtxFieldAttr {
+    #[allow(unused)]
+    name: Ident2,
+    value: Ident2,
+}
+
+impl CtxFieldAttr {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
+        let attr_arg_name = "ctx_field";
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let lit = attr_arg::parse_value_lit_str(attr_iter, &name)?;
+        let value = lit.parse::<Ident2>().map_err(|_| syn::Error::new(
+            lit.span(),
+            format!("Expected a field name in attribute argument {name}"),
+        ))?;
+        Ok(Self { name, value })
+    }
+}
+
+// Currently ONLY recognizes the attribute arguments:
+// - ctx_vis = "<VISIBILITY>"
+#[derive(Debug)]
+struct CtxVisAttr {
+    name: Ident2,
+    value: Visibility,
+}
+
+impl CtxVisAttr {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
+        let attr_arg_name = "ctx_vis";
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let lit = attr_arg::parse_value_lit_str(attr_iter, &name)?;
+        let value = lit.parse::<Visibility>().map_err(|error| syn::Error::new(
+            lit.span(),
+            format!("Expected a visibility in attribute argument {name}: {error}"),
+        ))?;
+        Ok(Self { name, value })
+    }
+}
+
+// Currently ONLY recognizes the attribute arguments:
+// - disabled_ctx = "removed" | "zero_sized"
+#[derive(Debug)]
+struct DisabledCtxAttr {
+    name: Ident2,
+    value: DisabledCtx,
+}
+
+/// What happens to the `ErrorCtx` field when the build feature is disabled.
+#[derive(Debug)]
+enum DisabledCtx {
+    /// The field is removed with a `#[cfg]` attribute.
+    Removed,
+    /// The field is kept, but with the zero-sized type `NoErrorCtx`.
+    ZeroSized,
+}
+
+impl DisabledCtxAttr {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
+        let attr_arg_name = "disabled_ctx";
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let lit = attr_arg::parse_value_lit_str(attr_iter, &name)?;
+        let value = match &*lit.value() {
+            "removed" => DisabledCtx::Removed,
+            "zero_sized" => DisabledCtx::ZeroSized,
+            value => return Err(syn::Error::new(lit.span(), format!(
+                "Expected 'removed' or 'zero_sized', got '{value}'"
+            ))),
+        };
+        Ok(Self { name, value })
+    }
+}
+
+// Currently ONLY recognizes the attribute arguments:
+// - render = "message" | "single_line" | "full"
+#[derive(Debug)]
+struct RenderAttr {
+    #[allow(unused)]
+    name: Ident2,
+    value: RenderStyle,
+}
+
+/// The `RenderStyle` of the type, see err-marks-the-spot-core.
+#[derive(Debug)]
+enum RenderStyle {
+    Message,
+    SingleLine,
+    Full,
+}
+
+impl RenderStyle {
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Message => "Message",
+            Self::SingleLine => "SingleLine",
+            Self::Full => "Full",
+        }
+    }
+}
+
+impl RenderAttr {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
+        let attr_arg_name = "render";
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let lit = attr_arg::parse_value_lit_str(attr_iter, &name)?;
+        let value = match &*lit.value() {
+            "message" => RenderStyle::Message,
+            "single_line" => RenderStyle::SingleLine,
+            "full" => RenderStyle::Full,
+            value => return Err(syn::Error::new(lit.span(), format!(
+                "Expected 'message', 'single_line' or 'full', got '{value}'"
+            ))),
+        };
+        Ok(Self { name, value })
+    }
+}
+
+// Currently ONLY recognizes the attribute arguments:
+// - backtrace = "never" | "always" | "env" | "cached"
+#[derive(Debug)]
+struct BacktraceAttr {
+    #[allow(unused)]
+    name: Ident2,
+    value: BacktraceCapture,
+}
+
+/// When the `ErrorCtx` field captures a backtrace.
+#[derive(Debug)]
+enum BacktraceCapture {
+    Never,
+    Always,
+    Env,
+    Cached,
+}
+
+impl BacktraceCapture {
+    /// The name of the corresponding `CapturePolicy` variant,
+    /// see err-marks-the-spot-core.
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Never => "LocationOnly",
+            Self::Always => "Force",
+            Self::Env => "Env",
+            Self::Cached => "Cached",
+        }
+    }
+}
+
+impl BacktraceAttr {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
+        let attr_arg_name = "backtrace";
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let lit = attr_arg::parse_value_lit_str(attr_iter, &name)?;
+        let value = match &*lit.value() {
+            "never" => BacktraceCapture::Never,
+            "always" => BacktraceCapture::Always,
+            "env" => BacktraceCapture::Env,
+            "cached" => BacktraceCapture::Cached,
+            value => return Err(syn::Error::new(lit.span(), format!(
+                "Expected 'never', 'always', 'env' or 'cached', got '{value}'"
+            ))),
+        };
+        Ok(Self { name, value })
+    }
+}
+
+// Currently ONLY recognizes the attribute arguments:
+// - ctx_repr = "full" | "thin"
+#[derive(Debug)]
+struct CtxReprAttr {
+    #[allow(unused)]
+    name: Ident2,
+    value: CtxRepr,
+}
+
+/// The type of the context field.
+#[derive(Debug)]
+enum CtxRepr {
+    /// `ErrorCtx`, which holds the location and backtrace inline.
+    Full,
+    /// The pointer-sized `ThinErrorCtx`.
+    Thin,
+}
+
+impl CtxReprAttr {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
+        let attr_arg_name = "ctx_repr";
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let lit = attr_arg::parse_value_lit_str(attr_iter, &name)?;
+        let value = match &*lit.value() {
+            "full" => CtxRepr::Full,
+            "thin" => CtxRepr::Thin,
+            value => return Err(syn::Error::new(lit.span(), format!(
+                "Expected 'full' or 'thin', got '{value}'"
+            ))),
+        };
+        Ok(Self { name, value })
+    }
+}
+
+// Currently ONLY recognizes the attribute arguments:
+// - serde = "skip" | "ctx"
+#[derive(Debug)]
+struct SerdeAttr {
+    #[allow(unused)]
+    name: Ident2,
+    value: SerdeCtx,
+}
+
+/// How serde derives on the type handle the context field.
+#[derive(Clone, Copy, Debug)]
+enum SerdeCtx {
+    /// The field is skipped, and deserialized errors get a placeholder.
+    Skip,
+    /// The field is (de)serialized with the schema of `OwnedErrorCtx`, and
+    /// errors that are deserialized without it get a placeholder.
+    Ctx,
+}
+
+impl SerdeAttr {
+    fn parse(attr_iter: &mut Peekable<IntoIter>) -> syn::Result<Self> {
+        let attr_arg_name = "serde";
+        let (name, _stream) = attr_arg::parse_name(attr_iter, attr_arg_name)?;
+        attr_arg::parse_eq_token(attr_iter, &name)?;
+        let lit = attr_arg::parse_value_lit_str(attr_iter, &name)?;
+        let value = match &*lit.value() {
+            "skip" => SerdeCtx::Skip,
+            "ctx" => SerdeCtx::Ctx,
+            value => return Err(syn::Error::new(lit.span(), format!(
+                "Expected 'skip' or 'ctx', got '{value}'"
+            ))),
+        };
+        Ok(Self { name, value })
+    }
+}
+
 mod attr_arg {
     use super::*;
 
     pub fn parse_name(
         attr_iter: &mut Peekable<IntoIter>,
         attr_arg_name: &str,
-    ) -> (Ident2, TokenStream2) {
+    ) -> syn::Result<(Ident2, TokenStream2)> {
         let attr_name_tt: TokenTree = attr_iter.next().unwrap();
         assert_eq!(attr_name_tt.to_string(), attr_arg_name);
+        let span = Span2::from(attr_name_tt.span());
         let name_stream: TokenStream2 = TokenStream::from(attr_name_tt).into();
-        let name: Ident2 = syn::parse2(name_stream.clone()).unwrap_or_else(|_| {
-            panic!("Failed to parse attribute argument: {attr_arg_name}")
-        });
-        (name, name_stream)
+        // NOTE: Some attribute argument names, e.g. `crate`, are keywords:
+        let name: Ident2 = Ident2::parse_any.parse2(name_stream.clone()).map_err(|_| {
+            syn::Error::new(span, format!(
+                "Failed to parse attribute argument: {attr_arg_name}"
+            ))
+        })?;
+        Ok((name, name_stream))
     }
 
     pub fn parse_eq_token(
         attr_iter: &mut Peekable<IntoIter>,
-        attr_arg_name: &str,
-    ) {
-        let _eq_tt = attr_iter.next();
-        let _eq = match _eq_tt {
-            Some(TokenTree::Punct(p)) => {
-                assert_eq!(p.as_char(), '=', "{attr_arg_name}");
-            },
-            Some(tt) => panic!("Unrecognized token tree: {tt}"),
-            None => panic!("Expected '='"),
-        };
+        attr_arg_name: &Ident2,
+    ) -> syn::Result<()> {
+        match attr_iter.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == '=' => Ok(()),
+            Some(tt) => Err(syn::Error::new(tt.span().into(), format!(
+                "Expected '=' after attribute argument {attr_arg_name}, got '{tt}'"
+            ))),
+            None => Err(syn::Error::new(attr_arg_name.span(), format!(
+                "Expected '=' after attribute argument {attr_arg_name}"
+            ))),
+        }
     }
 
     pub fn parse_value_expr(
         attr_iter: &mut Peekable<IntoIter>,
-        attr_arg_name: &str,
-    ) -> Expr {
+        attr_arg_name: &Ident2,
+    ) -> syn::Result<Expr> {
         match attr_iter.next() {
             Some(tt @ TokenTree::Literal(_)) => {
+                let span = Span2::from(tt.span());
                 let stream: TokenStream2 = TokenStream::from(tt).into();
-                syn::parse2::<Expr>(stream).unwrap_or_else(|_| panic!(
-                    "Failed to parse value expr of attribute argument {}",
-                    attr_arg_name
+                syn::parse2::<Expr>(stream).map_err(|_| syn::Error::new(
+                    span,
+                    format!(
+                        "Failed to parse value expr of attribute argument {}",
+                        attr_arg_name
+                    ),
                 ))
             }
-            Some(tt) => panic!(
-                "Expected value expr of attribute argument {}, got token tree {}",
+            Some(tt) => Err(syn::Error::new(tt.span().into(), format!(
+                "Expected value expr of attribute argument {}, got '{}'",
                 attr_arg_name, tt
-            ),
-            None => panic!(
+            ))),
+            None => Err(syn::Error::new(attr_arg_name.span(), format!(
                 "Expected value expr of attribute argument {}",
                 attr_arg_name,
-            ),
+            ))),
+        }
+    }
+
+    pub fn parse_value_lit_str(
+        attr_iter: &mut Peekable<IntoIter>,
+        attr_arg_name: &Ident2,
+    ) -> syn::Result<LitStr> {
+        match parse_value_expr(attr_iter, attr_arg_name)? {
+            Expr::Lit(ExprLit { lit: Lit::Str(lit), .. }) => Ok(lit),
+            expr => Err(syn::Error::new_spanned(expr, format!(
+                "Expected a string literal in attribute argument {attr_arg_name}"
+            ))),
         }
     }
 
     pub fn parse_parenthesized_value_ident(
         attr_iter: &mut Peekable<IntoIter>,
-    ) -> Option<Ident2> {
-        match attr_iter.next() {
+    ) -> syn::Result<Option<Ident2>> {
+        match attr_iter.peek() {
             Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => {
+                let span = Span2::from(g.span());
                 let inner_stream = TokenStream2::from(g.stream());
+                attr_iter.next();
                 let attr_arg_value = syn::parse2::<Ident2>(inner_stream)
-                    .expect("Expected a parenthesized value ident");
-                Some(attr_arg_value)
+                    .map_err(|_| syn::Error::new(
+                        span,
+                        "Expected a parenthesized value ident",
+                    ))?;
+                Ok(Some(attr_arg_value))
             },
-            _ => None,
+            _ => Ok(None),
         }
     }
 
-    pub fn parse_comma_token(attr_iter: &mut Peekable<IntoIter>) {
-        let _comma_tt = attr_iter.next();
-        let _comma = match _comma_tt {
-            Some(TokenTree::Punct(p)) => assert_eq!(p.as_char(), ','),
-            Some(tt) => panic!("Unrecognized token tree: {tt}"),
-            None => panic!("Expected ','"),
-        };
+    /// Parse the ',' that separates attribute arguments,
+    /// unless the end of the attribute arguments is reached.
+    pub fn parse_comma_token(
+        attr_iter: &mut Peekable<IntoIter>,
+    ) -> syn::Result<()> {
+        match attr_iter.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == ',' => Ok(()),
+            Some(tt) => Err(syn::Error::new(tt.span().into(), format!(
+                "Expected ',', got '{tt}'"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn skip_past_comma(attr_iter: &mut Peekable<IntoIter>) {
+        for tt in attr_iter.by_ref() {
+            if matches!(&tt, TokenTree::Punct(p) if p.as_char() == ',') {
+                break;
+            }
+        }
+    }
+
+    /// Store an attribute argument, unless it was already provided.
+    pub fn set_once<T>(
+        slot: &mut Option<T>,
+        arg: T,
+        span: Span2,
+    ) -> syn::Result<()> {
+        if slot.is_some() {
+            return Err(syn::Error::new(span, "Duplicate attribute argument"));
+        }
+        *slot = Some(arg);
+        Ok(())
     }
 }
 
+/// Generate the `ErrorMessage` impl, which writes the docstrings with their
+/// placeholders filled in, and the `Display` impl that renders the message
+/// in a `RenderStyle`.
 fn gen_impl_Display_for_type(
-    build_feature: Option<&BuildFeatureAttr>,
+    crate_path: &Path,
+    render_style: &TokenStream2,
+    ctx_name: &Ident2,
     type_item: &DeriveInput,
-) -> TokenStream2 {
+) -> syn::Result<TokenStream2> {
     let type_item_name = &type_item.ident;
-    let type_item_docstrs: Vec<String> = get_docstrs_from_attrs(&type_item.attrs);
+    let type_item_docstrs: Vec<(String, LitStr)> =
+        get_docstrs_from_attrs(&type_item.attrs)?;
     let item_field_map: FieldMap = match &type_item.data {
-        Data::Union(_) => panic!("Unions are not supported"),
+        Data::Union(_) => unreachable!("Unions are rejected up front"),
         Data::Enum(e) => {
             let enum_fields_map = e.variants
                 .iter()
                 .map(|Variant { ident, fields, .. }| (
                     ident.clone(),
-                    create_fields_map(&fields)
+                    create_fields_map(fields, ctx_name)
                 ))
                 .collect();
             FieldMap::Enum(enum_fields_map)
         }
-        Data::Struct(s) => FieldMap::Struct(create_fields_map(&s.fields)),
+        Data::Struct(s) => FieldMap::Struct(create_fields_map(&s.fields, ctx_name)),
     };
     let struct_impl_Display_contents = get_struct_impl_Display_contents(
-        build_feature,
         type_item,
         &type_item_docstrs,
         &item_field_map,
-    );
+    )?;
     let enum_impl_Display_contents = get_enum_impl_Display_contents(
-        build_feature,
         type_item,
         &item_field_map,
-    );
+    )?;
     let impl_Display_contents = if let FieldMap::Struct(_) = item_field_map {
         quote! { #struct_impl_Display_contents }
     } else {
         quote! { #enum_impl_Display_contents }
     };
-    quote! {
-        impl std::fmt::Display for #type_item_name {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut generics = type_item.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field_ty in display_bounded_field_types(type_item)? {
+        where_clause.predicates.push(parse_quote! {
+            #field_ty: std::fmt::Display
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #crate_path::ErrorMessage
+        for #type_item_name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn fmt_message(
+                &self,
+                f: &mut std::fmt::Formatter<'_>,
+            ) -> std::fmt::Result {
                 #impl_Display_contents
                 Ok(())
             }
         }
+
+        impl #impl_generics std::fmt::Display
+        for #type_item_name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                #crate_path::RenderStyle::select(f, #render_style)
+                    .fmt_error(self, f)
+            }
+        }
+    })
+}
+
+fn gen_impl_Error_for_type(type_item: &DeriveInput) -> syn::Result<TokenStream2> {
+    let type_item_name = &type_item.ident;
+    let source_body = match &type_item.data {
+        Data::Union(_) => unreachable!("Unions are rejected up front"),
+        Data::Struct(s) => match find_source_field(&s.fields)? {
+            Some(source) => quote! {
+                Some(&self . #source as &(dyn std::error::Error + 'static))
+            },
+            None => quote! { None },
+        },
+        Data::Enum(e) => {
+            let arms = e.variants.iter()
+                .map(|Variant { ident: variant_name, fields, .. }| {
+                    Ok(match (fields, find_source_field(fields)?) {
+                        (Fields::Named(_), Some(source)) => quote! {
+                            Self :: #variant_name { #source: source, .. } =>
+                                Some(source as &(dyn std::error::Error + 'static)),
+                        },
+                        (Fields::Unnamed(_), Some(FieldIdToken::Literal(lit))) => {
+                            let skipped = (0..lit.base10_parse::<usize>().unwrap())
+                                .map(|_| quote! { _ });
+                            quote! {
+                                Self :: #variant_name ( #(#skipped ,)* source, .. ) =>
+                                    Some(source as &(dyn std::error::Error + 'static)),
+                            }
+                        },
+                        _ => quote! {
+                            Self :: #variant_name { .. } => None,
+                        },
+                    })
+                });
+            let arms = Errors::collect(arms)?;
+            quote! {
+                match self {
+                    #( #arms )*
+                }
+            }
+        },
+    };
+    let mut generics = type_item.generics.clone();
+    if generics.type_params().next().is_some() {
+        // The Debug and Display impls may be conditional on the type params:
+        generics.make_where_clause().predicates.push(parse_quote! {
+            Self: std::fmt::Debug + std::fmt::Display
+        });
+    }
+    for field_ty in source_field_types(type_item) {
+        if mentions_type_param(field_ty, &type_item.generics) {
+            generics.make_where_clause().predicates.push(parse_quote! {
+                #field_ty: std::error::Error + 'static
+            });
+        }
     }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics std::error::Error
+        for #type_item_name #ty_generics #where_clause {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                #source_body
+            }
+        }
+    })
 }
 
-fn get_struct_impl_Display_contents(
+/// Generate a `From` impl for each enum variant with a `#[from]` field.
+/// The impls construct the variant using its generated ctor, so that the
+/// `ErrorCtx` points to the site where the conversion happened, e.g. the
+/// location of a `?` operator.
+fn gen_impl_From_for_type(type_item: &DeriveInput) -> syn::Result<TokenStream2> {
+    let type_item_name = &type_item.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        type_item.generics.split_for_impl();
+    let impls: Vec<syn::Result<TokenStream2>> = match &type_item.data {
+        Data::Union(_) => unreachable!("Unions are rejected up front"),
+        Data::Struct(s) => s.fields.iter()
+            .filter_map(|field| find_helper_attr(field, "from"))
+            .map(|attr| Err(syn::Error::new_spanned(
+                attr,
+                "#[from] is only supported on enum variant fields",
+            )))
+            .collect(),
+        Data::Enum(e) => e.variants.iter()
+            .filter(|Variant { fields, .. }| fields.iter().any(is_from_field))
+            .map(|Variant { ident: variant_name, fields, .. }| {
+                let [Field { ty, .. }] = &*fields.iter().collect::<Vec<_>>()
+                else {
+                    return Err(syn::Error::new(
+                        variant_name.span(),
+                        format!(
+                            "Variant {}::{} has a #[from] field, \
+                             so it must have exactly 1 field",
+                            type_item_name, variant_name
+                        ),
+                    ));
+                };
+                let ctor_name = format!("new_{variant_name}");
+                let ctor_name = Ident2::new(&ctor_name, Span2::call_site());
+                Ok(quote! {
+                    impl #impl_generics From<#ty>
+                    for #type_item_name #ty_generics #where_clause {
+                        #[track_caller]
+                        fn from(source: #ty) -> Self {
+                            Self :: #ctor_name(source)
+                        }
+                    }
+                })
+            })
+            .collect(),
+    };
+    let impls = Errors::collect(impls)?;
+    Ok(quote! { #(#impls)* })
+}
+
+fn gen_impl_HasErrorCtx_for_type(
+    ctx_field: &CtxField,
     build_feature: Option<&BuildFeatureAttr>,
     type_item: &DeriveInput,
-    type_item_docstrs: &[String],
-    item_field_map: &FieldMap,
 ) -> TokenStream2 {
+    let CtxField { crate_path, name: ctx_name, thin, .. } = ctx_field;
     let type_item_name = &type_item.ident;
-    let quotes: Vec<TokenStream2> = type_item_docstrs.iter()
+    let (impl_generics, ty_generics, where_clause) =
+        type_item.generics.split_for_impl();
+    // An expression that evaluates to the ErrorCtx field, given the way in
+    // which it is accessed i.e. `&`, `&mut` or by value. A `ThinErrorCtx`
+    // field is converted by calling `thin_method` on it instead:
+    let ctx_access = |access: TokenStream2, thin_method: &str| {
+        let (access, thin_method) = if *thin {
+            let thin_method = Ident2::new(thin_method, Span2::call_site());
+            (quote! {}, quote! { . #thin_method () })
+        } else {
+            (access, quote! {})
+        };
+        match &type_item.data {
+            Data::Union(_) => unreachable!("Unions are rejected up front"),
+            Data::Struct(s) => match &s.fields {
+                Fields::Named(_) | Fields::Unit => quote! {
+                    Some(#access self . #ctx_name #thin_method)
+                },
+                Fields::Unnamed(u) => {
+                    let ctx_field = LitInt::new(
+                        &u.unnamed.len().to_string(),
+                        Span2::call_site(),
+                    );
+                    quote! { Some(#access self . #ctx_field #thin_method) }
+                },
+            },
+            Data::Enum(e) => {
+                let arms = e.variants.iter()
+                    .map(|Variant { ident: variant_name, fields, .. }| {
+                        match fields {
+                            Fields::Named(_) | Fields::Unit => quote! {
+                                Self :: #variant_name { #ctx_name, .. } =>
+                                    Some(#ctx_name #thin_method),
+                            },
+                            Fields::Unnamed(u) => {
+                                let skipped = u.unnamed.iter().map(|_| quote! { _ });
+                                quote! {
+                                    Self :: #variant_name ( #(#skipped ,)* #ctx_name ) =>
+                                        Some(#ctx_name #thin_method),
+                                }
+                            },
+                        }
+                    });
+                quote! {
+                    match self {
+                        #( #arms )*
+                    }
+                }
+            },
+        }
+    };
+    let ctx = ctx_access(quote! { & }, "as_ctx");
+    let ctx_mut = ctx_access(quote! { &mut }, "as_ctx_mut");
+    let take_ctx = ctx_access(quote! {}, "into_ctx");
+    let methods = |cfg: TokenStream2, ctx, ctx_mut, take_ctx| quote! {
+        #cfg
+        fn ctx(&self) -> Option<&#crate_path::ErrorCtx> {
+            #ctx
+        }
+
+        #cfg
+        fn ctx_mut(&mut self) -> Option<&mut #crate_path::ErrorCtx> {
+            #ctx_mut
+        }
+
+        #cfg
+        fn take_ctx(self) -> Option<#crate_path::ErrorCtx> {
+            #take_ctx
+        }
+    };
+    let methods = if let Some(BuildFeatureAttr { value, .. }) = build_feature {
+        // Without the build feature, the type has no ErrorCtx field:
+        let with_ctx = methods(
+            quote! { #[cfg(feature = #value)] },
+            ctx, ctx_mut, take_ctx,
+        );
+        let without_ctx = methods(
+            quote! { #[cfg(not(feature = #value))] },
+            quote! { None }, quote! { None }, quote! { None },
+        );
+        quote! { #with_ctx #without_ctx }
+    } else {
+        methods(quote! {}, ctx, ctx_mut, take_ctx)
+    };
+    quote! {
+        impl #impl_generics #crate_path::HasErrorCtx
+        for #type_item_name #ty_generics #where_clause {
+            #methods
+        }
+    }
+}
+
+/// Return the types of all `#[source]` and `#[from]` fields.
+fn source_field_types(type_item: &DeriveInput) -> Vec<&Type> {
+    let fields: Vec<&Fields> = match &type_item.data {
+        Data::Union(_) => unreachable!("Unions are rejected up front"),
+        Data::Struct(s) => vec![&s.fields],
+        Data::Enum(e) => e.variants.iter().map(|v| &v.fields).collect(),
+    };
+    fields.into_iter()
+        .flat_map(|fields| fields.iter())
+        .filter(|field| {
+            find_helper_attr(field, "source").is_some() || is_from_field(field)
+        })
+        .map(|Field { ty, .. }| ty)
+        .collect()
+}
+
+/// Return the types of the fields that are used in docstring placeholders,
+/// and which mention a generic type param of the type.  Each of those needs
+/// a `Display` bound on the generated `Display` impl.
+fn display_bounded_field_types(
+    type_item: &DeriveInput,
+) -> syn::Result<Vec<&Type>> {
+    let docstrs_with_fields: Vec<(Vec<(String, LitStr)>, &Fields)> =
+        match &type_item.data {
+            Data::Union(_) => unreachable!("Unions are rejected up front"),
+            Data::Struct(s) => vec![
+                (get_docstrs_from_attrs(&type_item.attrs)?, &s.fields)
+            ],
+            Data::Enum(e) => Errors::collect(e.variants.iter()
+                .map(|v| Ok((get_docstrs_from_attrs(&v.attrs)?, &v.fields))))?,
+        };
+    let mut field_types: Vec<&Type> = vec![];
+    for (docstrs, fields) in docstrs_with_fields {
+        for (docstr, _lit) in &docstrs {
+            for (_pos, field_name) in find_docstring_fields(docstr) {
+                let field = fields.iter().enumerate()
+                    .find(|(i, Field { ident, .. })| match ident {
+                        Some(ident) => ident == field_name,
+                        None => i.to_string() == field_name,
+                    });
+                let Some((_, Field { ty, .. })) = field else { continue };
+                if mentions_type_param(ty, &type_item.generics)
+                    && !field_types.contains(&ty)
+                {
+                    field_types.push(ty);
+                }
+            }
+        }
+    }
+    Ok(field_types)
+}
+
+/// Determine whether a type mentions any of the generic type params.
+fn mentions_type_param(ty: &Type, generics: &Generics) -> bool {
+    fn mentions(stream: TokenStream2, params: &[&Ident2]) -> bool {
+        stream.into_iter().any(|tt| match tt {
+            TokenTree2::Ident(ident) => params.contains(&&ident),
+            TokenTree2::Group(group) => mentions(group.stream(), params),
+            _ => false,
+        })
+    }
+    let params: Vec<&Ident2> = generics.type_params()
+        .map(|param| &param.ident)
+        .collect();
+    mentions(ty.to_token_stream(), &params)
+}
+
+fn find_helper_attr<'f>(field: &'f Field, name: &str) -> Option<&'f Attribute> {
+    field.attrs.iter().find(|attr| is_helper_attr(attr, name))
+}
+
+fn is_from_field(field: &Field) -> bool {
+    find_helper_attr(field, "from").is_some()
+}
+
+/// Find the field that is marked with `#[source]`, if any.
+/// A `#[from]` field is implicitly also a `#[source]` field.
+fn find_source_field(fields: &Fields) -> syn::Result<Option<FieldIdToken>> {
+    let mut source_fields = fields.iter()
+        .enumerate()
+        .filter_map(|(i, field)| {
+            let attr = find_helper_attr(field, "source")
+                .or_else(|| find_helper_attr(field, "from"))?;
+            Some((i, field, attr))
+        })
+        .map(|(i, Field { ident, .. }, attr)| {
+            let (_, token) = single_field_mapping(ident.clone(), Some(i));
+            (token, attr)
+        });
+    let source_field = source_fields.next();
+    let mut errors = Errors::default();
+    for (_, attr) in source_fields {
+        errors.push(syn::Error::new_spanned(
+            attr,
+            "At most one field can be marked with #[source] or #[from]",
+        ));
+    }
+    errors.finish()?;
+    Ok(source_field.map(|(token, _)| token))
+}
+
+fn get_struct_impl_Display_contents(
+    type_item: &DeriveInput,
+    type_item_docstrs: &[(String, LitStr)],
+    item_field_map: &FieldMap,
+) -> syn::Result<TokenStream2> {
+    let type_item_name = &type_item.ident;
+    let docstr_writes = type_item_docstrs.iter()
         .filter(|_| matches!(item_field_map, FieldMap::Struct(_)))
-        .map(|item_docstr| {
+        .enumerate()
+        .map(|(idx, (item_docstr, docstr_lit))| {
             let item_docstr_fields = find_docstring_fields(item_docstr);
             let modified_item_docstr = modify_docstr(
-                &item_docstr,
+                item_docstr,
                 &item_docstr_fields
             );
             let trimmed_item_docstr = LitStr::new(
@@ -748,59 +1684,54 @@ fn get_struct_impl_Display_contents(
             );
             assert!(matches!(type_item.data, Data::Struct(_)));
             let FieldMap::Struct(field_map) = &item_field_map else { unreachable!() };
-            let fields: Vec<&FieldIdToken> = item_docstr_fields.iter()
-                .map(|(_pos, field_name)| {
-                    field_map.get(*field_name).unwrap_or_else(|| panic!(
-                        "Type {} no has no field '{}'",
-                        type_item_name, field_name
+            let fields = item_docstr_fields.iter()
+                .map(|(pos, field_name)| {
+                    field_map.get(*field_name).ok_or_else(|| placeholder_error(
+                        docstr_lit,
+                        item_docstr,
+                        pos,
+                        format!(
+                            "Type {} has no field '{}' for docstring placeholder {{{}}}",
+                            type_item_name, field_name, field_name
+                        ),
                     ))
-                })
-                .collect();
-            quote! {
-                writeln!(
+                });
+            let fields: Vec<&FieldIdToken> = Errors::collect(fields)?;
+            let line_separator = line_separator(idx);
+            Ok(quote! {
+                #line_separator
+                write!(
                     f,
                     #trimmed_item_docstr,
                     #(&self . #fields),*
                 )?;
-            }
-        })
-        .chain([
-            if let Some(BuildFeatureAttr { name, value, .. }) = build_feature {
-                assert_eq!(name, &Ident2::new("feature", Span2::call_site()));
-                // Write an empty line between original msg & ErrorCtx, but
-                // only perform the writeln!() call if the consumer crate is
-                // built with the build feature enabled:
-                quote! {
-                    #[cfg(feature = #value)]
-                    writeln!(f, "")?;
-                }
-            } else {
-                // Write an empty line between original msg & ErrorCtx:
-                quote! { writeln!(f, "")?; }
-            },
-        ])
-        .chain(if let Data::Struct(s) = &type_item.data {
-            // ErrorCtx docstring extension:
-            vec![writeln_for_ErrorCtx_field(build_feature, &s.fields)]
-        } else {
-            vec![]
-        })
-        .collect();
-    quote! { #(#quotes)* }
+            })
+        });
+    let quotes: Vec<TokenStream2> = Errors::collect(docstr_writes)?;
+    Ok(quote! { #(#quotes)* })
+}
+
+/// The docstring lines of a message are separated by newlines,
+/// but the message has no trailing newline.
+fn line_separator(docstr_idx: usize) -> TokenStream2 {
+    if docstr_idx == 0 {
+        quote! {}
+    } else {
+        quote! { writeln!(f)?; }
+    }
 }
 
 fn get_enum_impl_Display_contents(
-    build_feature: Option<&BuildFeatureAttr>,
     type_item: &DeriveInput,
     item_field_map: &FieldMap,
-) -> TokenStream2 {
-    let FieldMap::Enum(field_map) = &item_field_map else { return quote!{} };
-    let Data::Enum(data) = &type_item.data else { return quote!{} };
+) -> syn::Result<TokenStream2> {
+    let FieldMap::Enum(field_map) = &item_field_map else { return Ok(quote!{}) };
+    let Data::Enum(data) = &type_item.data else { return Ok(quote!{}) };
     let DataEnum { variants, .. } = data;
 
-    let variant_writelns: Vec<_> = variants.iter()
+    let variant_writes = variants.iter()
         .map(|Variant { attrs, ident: variant_name, fields, .. }| {
-            let vdocstrs = get_docstrs_from_attrs(attrs);
+            let vdocstrs = get_docstrs_from_attrs(attrs)?;
 
             let vbindings: Vec<TokenStream2> = match fields {
                 Fields::Named(n) => n.named.iter()
@@ -822,138 +1753,139 @@ fn get_enum_impl_Display_contents(
                     .collect(),
             };
 
+            // NOTE: The `..` ignores the ErrorCtx field, which is rendered
+            //       separately (if it exists at all).
             let vbind_list = match fields {
-                Fields::Named(_)   => quote! { { #(#vbindings ,)*      } },
-                Fields::Unit       => quote! { { #(#vbindings ,)*      } },
-                Fields::Unnamed(_) => quote! { ( #(#vbindings ,)*      ) },
-            };
-            let vbind_list_with_ctx = match fields {
-                Fields::Named(_)   => quote! { { #(#vbindings ,)* ctx, } },
-                Fields::Unit       => quote! { { #(#vbindings ,)* ctx, } },
-                Fields::Unnamed(_) => quote! { ( #(#vbindings ,)* ctx, ) },
+                Fields::Named(_)   => quote! { { #(#vbindings ,)* .. } },
+                Fields::Unit       => quote! { { #(#vbindings ,)* .. } },
+                Fields::Unnamed(_) => quote! { ( #(#vbindings ,)* .. ) },
             };
 
-            let vdocstr_writelns: Vec<TokenStream2> = vdocstrs.iter()
-                .map(|variant_docstr| {
+            let vdocstr_writes = vdocstrs.iter()
+                .enumerate()
+                .map(|(idx, (variant_docstr, docstr_lit))| {
                     let variant_docstr_fields = find_docstring_fields(
                         variant_docstr
                     );
                     let modified_variant_docstr = modify_docstr(
-                        &variant_docstr,
+                        variant_docstr,
                         &variant_docstr_fields
                     );
                     let trimmed_variant_docstr = LitStr::new(
                         &modified_variant_docstr,
                         Span2::call_site()
                     );
-                    let variant_docstr_fields: Vec<Ident2> =
-                        variant_docstr_fields
+                    let variant_docstr_fields = variant_docstr_fields
                         .iter()
-                        .map(|(_pos, field_name)| {
-                            let enum_field_map = field_map.get(variant_name)
-                                .unwrap_or_else(|| panic!(
-                                    "Type {} no has no variant '{}'",
-                                    type_item.ident, variant_name
-                                ));
+                        .map(|(pos, field_name)| {
+                            let enum_field_map = &field_map[variant_name];
                             let field_token = enum_field_map
                                 .get(*field_name)
-                                .unwrap_or_else(|| panic!(
-                                    "Type variant {}::{} no has no field '{}'",
-                                    type_item.ident, variant_name, field_name
-                                ));
-                            match field_token {
+                                .ok_or_else(|| placeholder_error(
+                                    docstr_lit,
+                                    variant_docstr,
+                                    pos,
+                                    format!(
+                                        "Type variant {}::{} has no field '{}' \
+                                         for docstring placeholder {{{}}}",
+                                        type_item.ident, variant_name,
+                                        field_name, field_name
+                                    ),
+                                ))?;
+                            Ok(match field_token {
                                 FieldIdToken::Ident(ident) => ident.clone(),
                                 FieldIdToken::Literal(lit) => Ident2::new(
                                     &format!("f{lit}"),
                                     Span2::call_site()
                                 ),
-                            }
-                        })
-                        .collect();
-                    quote! {
-                        writeln!(
+                            })
+                        });
+                    let variant_docstr_fields: Vec<Ident2> =
+                        Errors::collect(variant_docstr_fields)?;
+                    let line_separator = line_separator(idx);
+                    Ok(quote! {
+                        #line_separator
+                        write!(
                             f,
                             #trimmed_variant_docstr,
                             #(& #variant_docstr_fields),*
                         )?;
-                    }
-                })
-                .chain([
-                    // Write an empty line between original msg & ErrorCtx,
-                    // but only perform the writeln!() call if the consumer
-                    // crate is built with the build feature enabled, or
-                    // without using the `feature` attribute argument:
-                    if let Some(bf) = build_feature {
-                        assert_eq!(
-                            bf.name,
-                            Ident2::new("feature", Span2::call_site())
-                        );
-                        let feature = &bf.value;
-                        quote! {
-                            #[cfg(feature = #feature)]
-                            writeln!(f, "")?;
-                            #[cfg(feature = #feature)]
-                            writeln!(f, "{}", &ctx)?;
-                        }
-                    } else {
-                        quote! {
-                            writeln!(f, "")?;
-                            writeln!(f, "{}", &ctx)?;
-                        }
-                    }
-                ])
-                .collect();
-
-            if let Some(bf) = build_feature {
-                let feature = &bf.value;
-                quote! {
-                    #[cfg(feature = #feature)]
-                    Self :: #variant_name  #vbind_list_with_ctx  => {
-                        #( #vdocstr_writelns )*
-                    },
-
-                    #[cfg(not(feature = #feature))]
-                    Self :: #variant_name  #vbind_list  => {
-                        #( #vdocstr_writelns )*
-                    },
-                }
-            } else {
-                quote! {
-                    Self :: #variant_name  #vbind_list_with_ctx  => {
-                        #( #vdocstr_writelns )*
-                    },
-                }
-            }
-
-        })
-        .collect();
+                    })
+                });
+            let vdocstr_writes: Vec<TokenStream2> =
+                Errors::collect(vdocstr_writes)?;
+            Ok(quote! {
+                Self :: #variant_name  #vbind_list  => {
+                    #( #vdocstr_writes )*
+                },
+            })
+        });
+    let variant_writes = Errors::collect(variant_writes)?;
 
-    quote! {
+    Ok(quote! {
         match self {
-            #( #variant_writelns )*
+            #( #variant_writes )*
         }
-    }
+    })
 }
 
-fn get_docstrs_from_attrs(attrs: &[Attribute]) -> Vec<String> {
-    attrs.iter()
-        .filter(|Attribute { meta, .. }| {
-            let Meta::NameValue(v) = meta else { return false };
+/// Return the docstring lines, along with the `#[doc]` literal of each.
+/// Docstrings that aren't a string literal, e.g. `#[doc = include_str!(..)]`,
+/// can't be rendered and are rejected.
+fn get_docstrs_from_attrs(
+    attrs: &[Attribute],
+) -> syn::Result<Vec<(String, LitStr)>> {
+    let docstrs = attrs.iter()
+        .filter_map(|Attribute { meta, .. }| {
+            let Meta::NameValue(v) = meta else { return None };
             let segs = v.path.segments.iter().collect::<Vec<_>>();
             let [PathSegment { ident, arguments: PathArguments::None }] = &*segs
-            else { return false };
-            ident.to_string() == "doc"
+            else { return None };
+            (ident == "doc").then_some(v)
         })
-        .map(|Attribute { meta, .. }|  {
-            let Meta::NameValue(v) = meta else { unreachable!() };
-            let Expr::Lit(ExprLit { lit, .. }) = &v.value else { unreachable!() };
-            let Lit::Str(s) = lit else { unreachable!() };
-            s.token().to_string()
-                .trim_start_matches(r#"" "#)
-                .trim_end_matches(r#"""#)
-                .to_string()
-        })
-        .collect()
+        .map(|v| {
+            let Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) = &v.value else {
+                return Err(syn::Error::new_spanned(
+                    &v.value,
+                    "Docstrings must be string literals, e.g. `/// Failed: {0}`; \
+                     computed docstrings such as `#[doc = include_str!(..)]` \
+                     are not supported",
+                ));
+            };
+            let docstr = s.value();
+            let docstr = docstr.strip_prefix(' ').unwrap_or(&docstr).to_string();
+            Ok((docstr, s.clone()))
+        });
+    Errors::collect(docstrs)
+}
+
+/// Create an error for the placeholder at `pos` in `docstr`, which is a line
+/// of the `#[doc]` literal `lit`.  The error points at the placeholder itself
+/// where the compiler supports that, or else at the whole docstring line.
+fn placeholder_error(
+    lit: &LitStr,
+    docstr: &str,
+    pos: &Range<usize>,
+    message: String,
+) -> syn::Error {
+    let value = lit.value();
+    let token = lit.token().to_string();
+    // NOTE: Offsets into the value only match offsets into the token if the
+    //       literal is a plain "..." string without escapes.
+    let is_verbatim = token.strip_prefix('"')
+        .and_then(|token| token.strip_suffix('"'))
+        .is_some_and(|token| token == value);
+    let start = 1 + (value.len() - docstr.len()) + pos.start;
+    let span = is_verbatim
+        .then(|| lit.token().subspan(start..start + pos.len()))
+        .flatten();
+    match span {
+        Some(span) => syn::Error::new(span, message),
+        None => syn::Error::new(
+            lit.span(),
+            format!("{message} (somewhere in this docstring line)"),
+        ),
+    }
 }
 
 /// Return a modified docstring that has each site where
@@ -988,56 +1920,6 @@ fn find_docstring_fields(docstr: &str) -> Vec<(Range<usize>, &str)> {
         .collect()
 }
 
-fn writeln_for_ErrorCtx_field(
-    build_feature: Option<&BuildFeatureAttr>,
-    fields: &Fields,
-) -> TokenStream2 {
-    match &fields {
-        Fields::Named(_) | Fields::Unit => {
-            let ident = Ident2::new("ctx", Span2::call_site());
-            let err_ctx_field = FieldIdToken::Ident(ident);
-            if let Some(BuildFeatureAttr { name, value, .. }) = build_feature {
-                assert_eq!(name, &Ident2::new("feature", Span2::call_site()));
-                // Write the error ctx, but only perform the writeln!() call if
-                // the consumer crate is built with the build feature enabled:
-                quote! {
-                    #[cfg(feature = #value)]
-                    writeln!(f, "{}", &self . #err_ctx_field)?;
-                }
-            } else {
-                // Write the error ctx
-                quote! {
-                    writeln!(f, "{}", &self . #err_ctx_field)?;
-                }
-            }
-        }
-        Fields::Unnamed(u) => {
-            let field_num = u.unnamed.iter()
-                .enumerate()
-                .last()
-                .map(|(field_num, _field)| field_num)
-                .unwrap_or_default();
-            let lit = format!("{}", field_num + 1);
-            let lit = LitInt::new(&lit, Span2::call_site());
-            let err_ctx_field = FieldIdToken::Literal(lit);
-            if let Some(BuildFeatureAttr { name, value, .. }) = build_feature {
-                assert_eq!(name, &Ident2::new("feature", Span2::call_site()));
-                // Write the error ctx, but only perform the writeln!() call if
-                // the consumer crate is built with the build feature enabled:
-                quote! {
-                    #[cfg(feature = #value)]
-                    writeln!(f, "{}", &self . #err_ctx_field)?;
-                }
-            } else {
-                // Write the error ctx
-                quote! {
-                    writeln!(f, "{}", &self . #err_ctx_field)?;
-                }
-            }
-        }
-    }
-}
-
 /// A quote!()-injectable token representing one of:
 /// - a name (for named fields), or
 /// - a number (for unnamd fields)
@@ -1080,21 +1962,22 @@ fn single_field_mapping(
     }
 }
 
-fn create_fields_map(fields: &Fields) -> HashMap<String, FieldIdToken> {
+fn create_fields_map(
+    fields: &Fields,
+    ctx_name: &Ident2,
+) -> HashMap<String, FieldIdToken> {
     match fields {
         Fields::Named(n) => n.named.iter()
             .map(|Field { ident, .. }| {
                 single_field_mapping(ident.clone(), None)
             })
             .chain({ // ErrorCtx field
-                let ident = Ident2::new("ctx", Span2::call_site());
-                [ single_field_mapping(Some(ident), None) ]
+                [ single_field_mapping(Some(ctx_name.clone()), None) ]
             })
             .collect(),
         Fields::Unit => std::iter::empty()
             .chain({ // ErrorCtx field
-                let ident = Ident2::new("ctx", Span2::call_site());
-                [ single_field_mapping(Some(ident), None) ]
+                [ single_field_mapping(Some(ctx_name.clone()), None) ]
             })
             .collect(),
         Fields::Unnamed(u) => {
@@ -0,0 +1,90 @@
+//! Tests for the `#[err_marks_the_spot]` macro in a crate that depends on
+//! `err-marks-the-spot` under another name, or only through a re-export.
+#![allow(unused)]
+
+use err_marks_the_spot_test::errors::reexport;
+
+/// Renamed: {0}
+#[errs::err_marks_the_spot(feature = "example-build-flag")]
+#[derive(Debug)]
+pub struct RenamedError(usize);
+
+#[errs::err_marks_the_spot(feature = "example-build-flag")]
+#[derive(Debug)]
+pub enum RenamedEnumError {
+    /// Renamed source: {0}
+    Renamed(#[from] RenamedError),
+}
+
+/// Renamed thin: {0}
+#[errs::err_marks_the_spot(
+    feature = "example-build-flag",
+    ctx_repr = "thin",
+    backtrace = "never",
+    render = "single_line"
+)]
+#[derive(Debug)]
+pub struct RenamedThinError(usize);
+
+/// Renamed zero-sized: {0}
+#[errs::err_marks_the_spot(
+    feature = "disabled-build-flag",
+    disabled_ctx = "zero_sized"
+)]
+#[derive(Debug)]
+pub struct RenamedZeroSizedError(usize);
+
+/// Reexported: {0}
+#[reexport::err_marks_the_spot(
+    feature = "example-build-flag",
+    crate = "::err_marks_the_spot_test::errors::reexport"
+)]
+#[derive(Debug)]
+pub struct ReexportedError(usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errs::{ErrorCtx, HasErrorCtx, NoErrorCtx, ThinErrorCtx};
+
+    #[test]
+    fn renamed_crate_path() {
+        let line = line!() + 1;
+        let renamed_error = RenamedError::new(1_usize);
+        assert!(renamed_error.to_string().starts_with("Renamed: 1"));
+        if let Some(ctx) = renamed_error.ctx() {
+            assert_eq!((ctx.file(), ctx.line()), (file!(), line));
+        }
+        #[cfg(feature = "example-build-flag")]
+        let _: &ErrorCtx = &renamed_error.1;
+
+        let enum_error = RenamedEnumError::from(renamed_error);
+        assert!(
+            enum_error
+                .to_string()
+                .starts_with("Renamed source: Renamed: 1")
+        );
+
+        let thin_error = RenamedThinError::new(2_usize);
+        assert!(thin_error.to_string().starts_with("Renamed thin: 2"));
+        #[cfg(feature = "example-build-flag")]
+        let _: &ThinErrorCtx = &thin_error.1;
+
+        let zero_sized_error = RenamedZeroSizedError::new(3_usize);
+        assert!(
+            zero_sized_error
+                .to_string()
+                .starts_with("Renamed zero-sized: 3")
+        );
+        #[cfg(not(feature = "disabled-build-flag"))]
+        let _: &NoErrorCtx = &zero_sized_error.1;
+    }
+
+    #[test]
+    fn reexported_crate_path() {
+        let reexported_error = ReexportedError::new(4_usize);
+        assert!(reexported_error.to_string().starts_with("Reexported: 4"));
+        #[cfg(feature = "example-build-flag")]
+        let _: &reexport::ErrorCtx = &reexported_error.1;
+    }
+}
@@ -1,7 +1,17 @@
-//!
+//! Tests for the `#[err_marks_the_spot]` macro.
 #![allow(unused)]
 
-use err_marks_the_spot::{ErrorCtx, err_marks_the_spot};
+use err_marks_the_spot::{
+    AnyError, BacktraceFrame, CapturePolicy, ColorPolicy, ContextExt,
+    CtxLocation, ErrorCtx, ErrorMessage, HasErrorCtx, Input, Located,
+    NoErrorCtx, Normalized, OptionExt, OwnedErrorCtx, RenderStyle, Report,
+    ReportBacktrace, ResultExt, SourceLocation, ThinErrorCtx,
+    err_marks_the_spot,
+};
+use serde::{Deserialize, Serialize};
+use std::backtrace::BacktraceStatus;
+use std::panic::Location;
+use std::path::Path;
 
 /// FOo qux baz {0} {1}
 ///   - BaR Quux {0}.
@@ -36,14 +46,12 @@ pub enum EnumError {
     Unit,
 }
 
-
-
 // /// An error container.
 #[err_marks_the_spot(feature = "example-build-flag")]
 #[derive(Debug)]
 pub enum ContainerContainerError {
     /// Container: {0}
-    ContainerError(ContainerError),
+    ContainerError(#[from] ContainerError),
 }
 
 // /// An error container.
@@ -51,7 +59,7 @@ pub enum ContainerContainerError {
 #[derive(Debug)]
 pub enum ContainerError {
     /// Blah: {0}
-    BlahError(BlahError),
+    BlahError(#[from] BlahError),
 }
 
 #[err_marks_the_spot(feature = "example-build-flag")]
@@ -64,6 +72,217 @@ pub struct BlahError {
     field1: usize,
 }
 
+/// A hand-written error that isn't annotated, but embeds its annotated
+/// source in its message.
+#[derive(Debug)]
+pub struct ForeignWrapperError(pub BlahError);
+
+impl std::fmt::Display for ForeignWrapperError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "foreign wrapper: {}", self.0)
+    }
+}
+
+impl std::error::Error for ForeignWrapperError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Foreign: {0}
+#[err_marks_the_spot(feature = "example-build-flag")]
+#[derive(Debug)]
+pub struct ForeignContainerError(#[source] ForeignWrapperError);
+
+/// A wrapper around a blah error: {inner}
+#[err_marks_the_spot(feature = "example-build-flag")]
+#[derive(Debug)]
+pub struct WrapperError {
+    #[source]
+    inner: BlahError,
+}
+
+/// Failed to parse '{input}' into {value}
+#[err_marks_the_spot(feature = "example-build-flag")]
+#[derive(Debug)]
+pub struct ParseError<'a, T: std::fmt::Debug> {
+    input: &'a str,
+    value: T,
+}
+
+#[err_marks_the_spot(feature = "example-build-flag")]
+#[derive(Debug)]
+pub enum GenericEnumError<E, const N: usize>
+where
+    E: std::fmt::Debug,
+{
+    /// Failed after {0} attempts
+    Attempts(usize, [u8; N]),
+    /// Wrapped: {inner}
+    Wrapped {
+        #[source]
+        inner: E,
+    },
+}
+
+/// Simulates a crate that re-exports `err_marks_the_spot` to its dependents.
+pub mod errors {
+    pub use err_marks_the_spot as reexport;
+}
+
+/// Reexported: {0}
+#[errors::reexport::err_marks_the_spot(
+    feature = "example-build-flag",
+    crate = "crate::errors::reexport"
+)]
+#[derive(Debug)]
+pub struct ReexportedError(usize);
+
+/// Renamed ctx field, next to a user-defined field called ctx: {ctx}
+#[err_marks_the_spot(
+    feature = "example-build-flag",
+    ctx_field = "origin",
+    ctx_vis = "pub(crate)"
+)]
+#[derive(Debug)]
+pub struct RenamedCtxStructError {
+    ctx: String,
+}
+
+#[err_marks_the_spot(feature = "example-build-flag", ctx_field = "origin")]
+#[derive(Debug)]
+pub enum RenamedCtxEnumError {
+    /// A user-defined field called ctx: {ctx}
+    Named { ctx: String },
+    /// A tuple variant: {0}
+    Tuple(usize),
+}
+
+/// Zero-sized when disabled: {f0}
+#[err_marks_the_spot(
+    feature = "example-build-flag",
+    disabled_ctx = "zero_sized"
+)]
+#[derive(Debug)]
+pub struct ZeroSizedCtxError {
+    f0: u64,
+}
+
+/// Zero-sized when disabled
+#[err_marks_the_spot(
+    feature = "disabled-build-flag",
+    disabled_ctx = "zero_sized"
+)]
+#[derive(Debug)]
+pub struct DisabledZeroSizedCtxError(u64);
+
+#[err_marks_the_spot(
+    feature = "disabled-build-flag",
+    disabled_ctx = "zero_sized"
+)]
+#[derive(Debug)]
+pub enum DisabledZeroSizedCtxEnumError {
+    /// Tuple: {0}
+    Tuple(u64),
+    /// Named: {f0}
+    Named { f0: u64 },
+    /// Unit
+    Unit,
+}
+
+/// Rendered as a message: {0}
+///   - on multiple lines
+#[err_marks_the_spot(feature = "example-build-flag", render = "message")]
+#[derive(Debug)]
+pub struct MessageRenderedError(usize);
+
+#[err_marks_the_spot(feature = "example-build-flag", render = "single_line")]
+#[derive(Debug)]
+pub enum SingleLineRenderedError {
+    /// Rendered on a single line: {0}
+    ///   - despite multiple lines
+    Tuple(usize),
+}
+
+/// Never captures a backtrace: {0}
+#[err_marks_the_spot(feature = "example-build-flag", backtrace = "never")]
+#[derive(Debug)]
+pub struct NeverBacktraceError(u64);
+
+#[err_marks_the_spot(feature = "example-build-flag", backtrace = "always")]
+#[derive(Debug)]
+pub enum AlwaysBacktraceError {
+    /// Always captures a backtrace: {0}
+    Tuple(u64),
+}
+
+/// Captures a backtrace once per call site: {0}
+#[err_marks_the_spot(feature = "example-build-flag", backtrace = "cached")]
+#[derive(Debug)]
+pub struct CachedBacktraceError(u64);
+
+/// Thin: {0}
+#[err_marks_the_spot(feature = "example-build-flag", ctx_repr = "thin")]
+#[derive(Debug)]
+pub struct ThinCtxError(usize);
+
+#[err_marks_the_spot(
+    feature = "example-build-flag",
+    ctx_repr = "thin",
+    backtrace = "always"
+)]
+#[derive(Debug)]
+pub enum ThinCtxEnumError {
+    /// Thin tuple: {0}
+    Tuple(usize),
+    /// Thin named: {f0}
+    Named { f0: usize },
+}
+
+// A thin context is pointer-sized, and so is an `Option` of it:
+const _: () = assert!(size_of::<ThinErrorCtx>() == size_of::<usize>());
+const _: () = assert!(size_of::<Option<ThinErrorCtx>>() == size_of::<usize>());
+#[cfg(feature = "example-build-flag")]
+const _: () = {
+    assert!(size_of::<ThinCtxError>() == 2 * size_of::<usize>());
+    assert!(size_of::<Result<(), ThinCtxError>>() == size_of::<ThinCtxError>());
+    assert!(size_of::<ThinCtxEnumError>() == 3 * size_of::<usize>());
+    assert!(size_of::<ErrorCtx>() > 2 * size_of::<ThinErrorCtx>());
+};
+
+/// Serialized with its context: {0}
+#[err_marks_the_spot(feature = "example-build-flag", serde = "ctx")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SerdeCtxError(u64);
+
+#[err_marks_the_spot(
+    feature = "example-build-flag",
+    ctx_repr = "thin",
+    serde = "ctx"
+)]
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SerdeThinCtxEnumError {
+    /// Serialized with its thin context: {f0}
+    Named { f0: u64 },
+    /// Serialized with its thin context
+    Unit,
+}
+
+/// Serialized without its context: {f0}
+#[err_marks_the_spot(feature = "example-build-flag", serde = "skip")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SerdeSkipCtxError {
+    f0: String,
+}
+
+/// Serialized without its zero-sized context: {0}
+#[err_marks_the_spot(
+    feature = "disabled-build-flag",
+    disabled_ctx = "zero_sized",
+    serde = "ctx"
+)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DisabledSerdeCtxError(u64);
 
 #[cfg(test)]
 mod tests {
@@ -148,4 +367,997 @@ mod tests {
         // let container_error = ContainerError::new_Blah2Error("blah error msg", 57_usize);
         // println!("{container_error}");
     }
+
+    #[test]
+    fn error_source() {
+        use std::error::Error;
+
+        let blah_error = BlahError::new("blah error msg", 57_usize);
+        assert!(blah_error.source().is_none());
+        let container_error = ContainerError::new_BlahError(blah_error);
+        let source = container_error.source().unwrap();
+        assert!(source.downcast_ref::<BlahError>().is_some());
+        let container_container_error =
+            ContainerContainerError::new_ContainerError(container_error);
+        let source = container_container_error.source().unwrap();
+        assert!(source.downcast_ref::<ContainerError>().is_some());
+        assert!(source.source().unwrap().is::<BlahError>());
+
+        let wrapper_error =
+            WrapperError::new(BlahError::new("wrapped", 1_usize));
+        assert!(wrapper_error.source().unwrap().is::<BlahError>());
+
+        let boxed: Box<dyn Error> = Box::new(EnumError::new_Unit());
+        assert!(boxed.source().is_none());
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn from_conversions() {
+        fn blah() -> Result<(), BlahError> {
+            Err(BlahError::new("blah error msg", 57_usize))
+        }
+        fn container() -> Result<(), ContainerError> {
+            blah()?;
+            Ok(())
+        }
+        fn container_container() -> Result<(), ContainerContainerError> {
+            container()?;
+            Ok(())
+        }
+
+        let container_container_error = container_container().unwrap_err();
+        println!("{container_container_error}");
+        #[cfg(feature = "example-build-flag")]
+        {
+            let ContainerContainerError::ContainerError(_, ctx) =
+                &container_container_error;
+            let expected_line = line!() - 10; // The `container()?` line
+            assert_eq!(ctx.file(), file!());
+            assert_eq!(ctx.line(), expected_line);
+            assert_eq!(ctx.column(), 13);
+        }
+    }
+
+    #[test]
+    fn generic_types() {
+        use std::error::Error;
+
+        let input = String::from("forty-two");
+        let parse_error: ParseError<u32> =
+            ParseError::new(input.as_str(), 42_u32);
+        let parse_error_msg = parse_error.to_string();
+        assert!(
+            parse_error_msg.starts_with("Failed to parse 'forty-two' into 42")
+        );
+        assert!(parse_error.source().is_none());
+
+        let attempts_error: GenericEnumError<BlahError, 2> =
+            GenericEnumError::new_Attempts(2_usize, [1, 2]);
+        assert!(
+            attempts_error
+                .to_string()
+                .starts_with("Failed after 2 attempts")
+        );
+        let wrapped_error: GenericEnumError<BlahError, 2> =
+            GenericEnumError::new_Wrapped(BlahError::new("wrapped", 3_usize));
+        assert!(
+            wrapped_error
+                .to_string()
+                .starts_with("Wrapped: This is a blah error;")
+        );
+        assert!(wrapped_error.source().unwrap().is::<BlahError>());
+    }
+
+    #[test]
+    fn crate_path() {
+        let reexported_error = ReexportedError::new(7_usize);
+        assert!(reexported_error.to_string().starts_with("Reexported: 7"));
+        #[cfg(feature = "example-build-flag")]
+        let _: &errors::reexport::ErrorCtx = &reexported_error.1;
+    }
+
+    #[test]
+    fn renamed_ctx_field() {
+        let struct_error = RenamedCtxStructError::new("user ctx");
+        assert!(struct_error.to_string().starts_with(
+            "Renamed ctx field, next to a user-defined field called ctx: \
+             user ctx"
+        ));
+        #[cfg(feature = "example-build-flag")]
+        let _: &ErrorCtx = &struct_error.origin;
+
+        let named_error = RenamedCtxEnumError::new_Named("user ctx");
+        assert!(
+            named_error
+                .to_string()
+                .starts_with("A user-defined field called ctx: user ctx")
+        );
+        #[cfg(feature = "example-build-flag")]
+        {
+            let RenamedCtxEnumError::Named { ctx, origin } = &named_error
+            else {
+                unreachable!()
+            };
+            assert_eq!(ctx, "user ctx");
+            assert!(origin.to_string().contains("lib.rs"));
+        }
+        let tuple_error = RenamedCtxEnumError::new_Tuple(5_usize);
+        assert!(tuple_error.to_string().starts_with("A tuple variant: 5"));
+    }
+
+    #[test]
+    fn has_error_ctx() {
+        let errors: Vec<Box<dyn HasErrorCtx>> = vec![
+            Box::new(TupleStructError::new(24_usize, "blahs")),
+            Box::new(NamedStructError::new("foo")),
+            Box::new(UnitStructError::new()),
+            Box::new(EnumError::new_Tuple(300_usize, "tuple", false)),
+            Box::new(EnumError::new_Named(42_u8, "named")),
+            Box::new(EnumError::new_Unit()),
+            Box::new(RenamedCtxEnumError::new_Tuple(5_usize)),
+        ];
+        for error in &errors {
+            let has_ctx = cfg!(feature = "example-build-flag");
+            assert_eq!(error.ctx().is_some(), has_ctx);
+        }
+
+        let mut named_struct_error = NamedStructError::new("foo");
+        let replacement_line = line!() + 2;
+        if let Some(ctx) = named_struct_error.ctx_mut() {
+            *ctx = ErrorCtx::new();
+        }
+        if let Some(ctx) = named_struct_error.take_ctx() {
+            assert_eq!(ctx.file(), file!());
+            assert_eq!(ctx.line(), replacement_line);
+            assert_eq!(ctx.column(), 20);
+        }
+    }
+
+    #[test]
+    fn zero_sized_disabled_ctx() {
+        // No #[cfg] attributes are needed in struct expressions or patterns:
+        let zero_sized_ctx_error = ZeroSizedCtxError {
+            f0: 42,
+            ctx: Default::default(),
+        };
+        let ZeroSizedCtxError { f0, ctx } = &zero_sized_ctx_error;
+        assert_eq!(*f0, 42);
+        assert_eq!(
+            zero_sized_ctx_error.ctx().is_some(),
+            cfg!(feature = "example-build-flag"),
+        );
+        println!("{ctx}");
+
+        // The "disabled-build-flag" feature is usually disabled, but it is
+        // enabled by e.g. `cargo test --all-features`:
+        let enabled = cfg!(feature = "disabled-build-flag");
+        let disabled_error = DisabledZeroSizedCtxError(7, Default::default());
+        let DisabledZeroSizedCtxError(_, ctx) = &disabled_error;
+        assert_eq!(disabled_error.ctx().is_some(), enabled);
+        let rendered = disabled_error.render(RenderStyle::Full).to_string();
+        if enabled {
+            assert!(rendered.starts_with("Zero-sized when disabled\n"));
+        } else {
+            assert_eq!(std::mem::size_of_val(ctx), 0);
+            assert_eq!(std::mem::size_of_val(&disabled_error), 8);
+            assert_eq!(rendered, "Zero-sized when disabled\n");
+        }
+
+        let disabled_enum_errors = [
+            DisabledZeroSizedCtxEnumError::new_Tuple(1_u64),
+            DisabledZeroSizedCtxEnumError::new_Named(2_u64),
+            DisabledZeroSizedCtxEnumError::new_Unit(),
+            DisabledZeroSizedCtxEnumError::Unit {
+                ctx: Default::default(),
+            },
+        ];
+        for error in &disabled_enum_errors {
+            assert_eq!(error.ctx().is_some(), enabled);
+        }
+        let rendered = disabled_enum_errors[1]
+            .render(RenderStyle::Full)
+            .to_string();
+        if enabled {
+            assert!(rendered.starts_with("Named: 2\n"));
+        } else {
+            assert_eq!(rendered, "Named: 2\n");
+        }
+    }
+
+    #[test]
+    fn error_ctx_accessors() {
+        let line = line!() + 1;
+        let error = BlahError::new("accessors", 1_usize);
+        let Some(ctx) = error.ctx() else { return };
+        assert!(ctx.file().ends_with("lib.rs"));
+        assert_eq!(ctx.line(), line);
+        assert_eq!(ctx.column(), 21);
+        assert_eq!(ctx.location().line(), line);
+
+        let frames: Vec<BacktraceFrame> = ctx.frames();
+        match ctx.backtrace_status() {
+            BacktraceStatus::Captured => {
+                assert!(!frames.is_empty());
+                let test_frame = frames
+                    .iter()
+                    .find(|frame| {
+                        frame.function().ends_with("error_ctx_accessors")
+                    })
+                    .expect("the test function should be in the backtrace");
+                assert!(test_frame.file().unwrap().ends_with("lib.rs"));
+                assert!(test_frame.line().is_some());
+            }
+            _ => assert!(frames.is_empty()),
+        }
+    }
+
+    /// Render `value` without ANSI color codes, regardless of the color policy.
+    fn strip_colors(value: &impl std::fmt::Display) -> String {
+        let value = value.to_string();
+        let mut plain = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn render_styles() {
+        let line = line!() + 1;
+        let message_error = MessageRenderedError::new(42_usize);
+        let location = format!("{}:{line}:29", file!());
+        let message = "Rendered as a message: 42\n  - on multiple lines";
+        assert_eq!(message_error.to_string(), message);
+        let full = strip_colors(&message_error.render(RenderStyle::Full));
+        let single_line = strip_colors(&format!("{message_error:#}"));
+        if cfg!(feature = "example-build-flag") {
+            assert_eq!(
+                single_line,
+                format!(
+                    "Rendered as a message: 42 - on multiple lines @ {location}"
+                ),
+            );
+            assert!(full.starts_with(&format!(
+                "{message}\n\nERROR detected @ {location}:\n"
+            )));
+        } else {
+            let single_line_message =
+                "Rendered as a message: 42 - on multiple lines";
+            assert_eq!(single_line, single_line_message);
+            assert_eq!(full, format!("{message}\n"));
+        }
+
+        let single_line_error = SingleLineRenderedError::new_Tuple(7_usize);
+        let single_line = strip_colors(&single_line_error);
+        assert!(single_line.starts_with(
+            "Rendered on a single line: 7 - despite multiple lines"
+        ));
+        assert!(!single_line.contains('\n'));
+        let message =
+            single_line_error.render(RenderStyle::Message).to_string();
+        assert_eq!(
+            message,
+            "Rendered on a single line: 7\n  - despite multiple lines",
+        );
+    }
+
+    #[test]
+    fn nested_render_styles() {
+        let line = line!() + 2;
+        let blah_error = BlahError::new("blah", 1_usize);
+        let container_error = ContainerError::new_BlahError(blah_error);
+        let location = format!("{}:{line}:31", file!());
+        let message =
+            "Blah: This is a blah error;\n  - field0 = blah\n  - field1 = 1";
+        let single_line_message =
+            "Blah: This is a blah error; - field0 = blah - field1 = 1";
+
+        // The nested error is rendered as a message in these styles:
+        let rendered = container_error.render(RenderStyle::Message).to_string();
+        assert_eq!(rendered, message);
+        let alternate = strip_colors(&format!("{container_error:#}"));
+        let single_line =
+            strip_colors(&container_error.render(RenderStyle::SingleLine));
+        assert_eq!(alternate, single_line);
+        if cfg!(feature = "example-build-flag") {
+            assert_eq!(
+                single_line,
+                format!("{single_line_message} @ {location}")
+            );
+        } else {
+            assert_eq!(single_line, single_line_message);
+        }
+
+        // ... and in full here:
+        let full = strip_colors(&container_error.render(RenderStyle::Full));
+        if cfg!(feature = "example-build-flag") {
+            assert!(
+                full.starts_with(&format!("{message}\n\nERROR detected @ "))
+            );
+            assert_eq!(full.matches("ERROR detected @ ").count(), 2);
+            assert!(full.contains(&format!("ERROR detected @ {location}:\n")));
+        } else {
+            assert_eq!(full, format!("{message}\n\n"));
+        }
+
+        // The style is only passed down while the message is rendered:
+        let blah_error = BlahError::new("blah", 1_usize);
+        assert_eq!(
+            blah_error.to_string(),
+            blah_error.render(RenderStyle::Message).to_string()
+        );
+    }
+
+    #[test]
+    fn backtrace_capture() {
+        let never = NeverBacktraceError::new(1_u64);
+        let always = AlwaysBacktraceError::new_Tuple(2_u64);
+        if cfg!(feature = "example-build-flag") {
+            let never_ctx = never.ctx().unwrap();
+            assert_eq!(never_ctx.backtrace_status(), BacktraceStatus::Disabled);
+            assert!(never_ctx.frames().is_empty());
+            let always_ctx = always.ctx().unwrap();
+            assert_eq!(
+                always_ctx.backtrace_status(),
+                BacktraceStatus::Captured
+            );
+            assert!(!always_ctx.frames().is_empty());
+        } else {
+            assert!(never.ctx().is_none());
+            assert!(always.ctx().is_none());
+        }
+    }
+
+    #[test]
+    fn cached_backtrace() {
+        let errors: Vec<CachedBacktraceError> =
+            (0..3_u64).map(CachedBacktraceError::new).collect();
+        let other_call_site_error = CachedBacktraceError::new(3_u64);
+        let ctxs: Vec<&ErrorCtx> =
+            errors.iter().filter_map(HasErrorCtx::ctx).collect();
+        let Some(other_call_site_ctx) = other_call_site_error.ctx() else {
+            assert!(ctxs.is_empty());
+            return;
+        };
+        for ctx in &ctxs {
+            assert_eq!(ctx.backtrace_status(), BacktraceStatus::Captured);
+            assert!(std::ptr::eq(ctx.backtrace(), ctxs[0].backtrace()));
+            assert_eq!(ctx.frames(), ctxs[0].frames());
+        }
+        let other_backtrace = other_call_site_ctx.backtrace();
+        assert!(!std::ptr::eq(other_backtrace, ctxs[0].backtrace()));
+    }
+
+    #[test]
+    fn thin_ctx() {
+        let line = line!() + 1;
+        let mut thin_error = ThinCtxError::new(5_usize);
+        assert!(thin_error.to_string().starts_with("Thin: 5"));
+        let Some(ctx) = thin_error.ctx() else { return };
+        assert_eq!(ctx.line(), line);
+
+        let replacement_line = line!() + 1;
+        *thin_error.ctx_mut().unwrap() = ErrorCtx::new();
+        assert_eq!(thin_error.ctx().unwrap().line(), replacement_line);
+        assert_eq!(thin_error.take_ctx().unwrap().line(), replacement_line);
+
+        let thin_enum_errors = [
+            ThinCtxEnumError::new_Tuple(1_usize),
+            ThinCtxEnumError::new_Named(2_usize),
+        ];
+        for error in &thin_enum_errors {
+            let ctx = error.ctx().unwrap();
+            assert_eq!(ctx.backtrace_status(), BacktraceStatus::Captured);
+            assert!(!ctx.frames().is_empty());
+        }
+        let [_, named] = thin_enum_errors;
+        assert!(named.to_string().starts_with("Thin named: 2"));
+        assert!(named.take_ctx().is_some());
+    }
+
+    #[test]
+    fn propagation_trail() {
+        fn load() -> Result<(), BlahError> {
+            Err(BlahError::new("trail", 1_usize))
+        }
+        fn parse() -> Result<(), BlahError> {
+            load().trace()?;
+            Ok(())
+        }
+        fn run() -> Result<(), BlahError> {
+            parse().trace()
+        }
+        fn run_thin() -> Result<(), ThinCtxError> {
+            Err(ThinCtxError::new(2_usize)).trace()
+        }
+
+        let error = run().unwrap_err();
+        let thin_error = run_thin().unwrap_err();
+        let (Some(ctx), Some(thin_ctx)) = (error.ctx(), thin_error.ctx())
+        else {
+            return;
+        };
+        let trail = ctx.trail();
+        assert_eq!(trail.len(), 2);
+        assert!(trail.iter().all(|location| location.file() == file!()));
+        assert!(ctx.line() < trail[0].line());
+        assert!(trail[0].line() < trail[1].line());
+        let rendered = strip_colors(&ctx);
+        let hops: Vec<&str> = rendered
+            .lines()
+            .filter(|line| line.starts_with("  propagated @ "))
+            .collect();
+        assert_eq!(
+            hops,
+            [
+                format!(
+                    "  propagated @ {}:{}:{}",
+                    file!(),
+                    trail[0].line(),
+                    trail[0].column()
+                ),
+                format!(
+                    "  propagated @ {}:{}:{}",
+                    file!(),
+                    trail[1].line(),
+                    trail[1].column()
+                ),
+            ]
+        );
+
+        assert_eq!(thin_ctx.trail().len(), 1);
+    }
+
+    #[test]
+    fn located_foreign_errors() {
+        use std::error::Error;
+        use std::num::ParseIntError;
+
+        const QUESTION_MARK_LINE: u32 = line!() + 2;
+        fn parse(input: &str) -> Result<u32, Located<ParseIntError>> {
+            Ok(input.parse::<u32>()?)
+        }
+
+        let line = line!() + 1;
+        let located = "x".parse::<u32>().located().unwrap_err();
+        assert_eq!(located.ctx().unwrap().line(), line);
+        assert_eq!(located.ctx().unwrap().file(), file!());
+        assert!(
+            located
+                .to_string()
+                .starts_with("invalid digit found in string")
+        );
+        assert_eq!(
+            located.render(RenderStyle::Message).to_string(),
+            located.error().to_string()
+        );
+        assert!(located.source().is_none());
+
+        let located = parse("y").unwrap_err();
+        assert_eq!(located.ctx().unwrap().line(), QUESTION_MARK_LINE);
+        assert_eq!(*located.error(), "y".parse::<u32>().unwrap_err());
+
+        // The source of the wrapped error is forwarded:
+        let blah_error = BlahError::new("blah", 1_usize);
+        let container_error = ContainerError::new_BlahError(blah_error);
+        let located = Err::<(), _>(container_error).located().unwrap_err();
+        let source = located.source().unwrap();
+        assert!(source.downcast_ref::<BlahError>().is_some());
+
+        let line = line!() + 1;
+        let located = None::<u8>.ok_or_located("missing").unwrap_err();
+        assert_eq!(located.ctx().unwrap().line(), line);
+        assert!(located.to_string().starts_with("missing"));
+        let line = line!() + 1;
+        let located = None::<u8>.ok_or_else_located(|| "missing").unwrap_err();
+        assert_eq!(located.ctx().unwrap().line(), line);
+        assert_eq!(Some(3_u8).ok_or_located("missing").unwrap(), 3);
+    }
+
+    #[test]
+    fn any_error() {
+        use std::num::ParseIntError;
+
+        const CONTEXT_LINE: u32 = line!() + 2;
+        fn load(input: &str) -> Result<u32, AnyError> {
+            input.parse::<u32>().located().context("failed to load")
+        }
+        fn start(input: &str) -> Result<u32, AnyError> {
+            load(input).with_context(|| format!("failed to start '{input}'"))
+        }
+
+        assert_eq!(size_of::<AnyError>(), size_of::<usize>());
+
+        // Converting an annotated error reuses its ctx:
+        let line = line!() + 1;
+        let blah_error = BlahError::new("blah", 1_usize);
+        let any_error = AnyError::from(blah_error);
+        let ctx_line = any_error.ctx().unwrap().line();
+        if cfg!(feature = "example-build-flag") {
+            assert_eq!(ctx_line, line);
+        } else {
+            // Without a ctx of its own, the conversion site is recorded:
+            assert_eq!(ctx_line, line + 1);
+        }
+        assert!(any_error.is::<BlahError>());
+        assert!(!any_error.is::<ParseIntError>());
+        assert!(any_error.downcast_ref::<BlahError>().is_some());
+        assert!(any_error.downcast_ref::<ParseIntError>().is_none());
+        let message = any_error.render(RenderStyle::Message).to_string();
+        assert_eq!(
+            message,
+            "This is a blah error;\n  - field0 = blah\n  - field1 = 1"
+        );
+        let blah_error = any_error.downcast::<BlahError>().unwrap();
+        let any_error = AnyError::from(blah_error);
+        let any_error = any_error.downcast::<ParseIntError>().unwrap_err();
+        assert!(any_error.take_ctx().is_some());
+
+        // Foreign errors are wrapped, and can be downcast:
+        let line = line!() + 1;
+        let mut any_error = AnyError::new("x".parse::<u8>().unwrap_err());
+        assert_eq!(any_error.ctx().unwrap().line(), line);
+        assert!(any_error.downcast_mut::<ParseIntError>().is_some());
+        assert_eq!(
+            any_error.to_string().lines().next(),
+            Some("invalid digit found in string")
+        );
+
+        // Context messages are stacked:
+        let any_error = start("y").unwrap_err();
+        assert!(any_error.downcast_ref::<ParseIntError>().is_some());
+        assert_eq!(any_error.ctx().unwrap().line(), CONTEXT_LINE);
+        let contexts: Vec<_> = any_error
+            .contexts()
+            .map(|(message, location)| (message, location.line()))
+            .collect();
+        assert_eq!(
+            contexts,
+            [
+                ("failed to load", CONTEXT_LINE),
+                ("failed to start 'y'", CONTEXT_LINE + 3),
+            ]
+        );
+        let message = "failed to start 'y': failed to load: \
+                       invalid digit found in string";
+        assert_eq!(any_error.render(RenderStyle::Message).to_string(), message);
+        let full = strip_colors(&any_error.render(RenderStyle::Full));
+        assert!(full.starts_with(&format!(
+            "{message}\n\nERROR detected @ {}:{CONTEXT_LINE}:",
+            file!()
+        )));
+        assert!(full.contains(&format!(
+            "Context:\n   0: failed to load\n             \
+             at {}:{CONTEXT_LINE}:",
+            file!(),
+        )));
+        assert!(full.contains(&format!(
+            "   1: failed to start 'y'\n             at {}:{}:",
+            file!(),
+            CONTEXT_LINE + 3,
+        )));
+    }
+
+    #[test]
+    fn any_error_as_dyn_error() {
+        use std::error::Error;
+
+        let blah_line = line!() + 2;
+        let container_error =
+            ContainerError::new_BlahError(BlahError::new("blah", 1_usize));
+        let any_error = AnyError::from(container_error).context("failed");
+        let blah = "This is a blah error; - field0 = blah - field1 = 1";
+
+        // A report renders the context messages and walks the source chain:
+        let report = strip_colors(&Report::new(any_error.as_dyn_error()));
+        let lines: Vec<_> = report.lines().collect();
+        assert!(lines[0].starts_with(&format!("failed: Blah: {blah} @ ")));
+        if cfg!(feature = "example-build-flag") {
+            assert!(
+                lines[0].contains(&format!(" @ {}:{blah_line}:", file!(),))
+            );
+            assert!(lines[1].starts_with(&format!(
+                "  caused by: {blah} @ {}:{blah_line}:",
+                file!(),
+            )));
+            assert_eq!(report.matches("ERROR detected").count(), 1);
+        } else {
+            assert_eq!(lines[1], format!("  caused by: {blah}"));
+        }
+
+        let dyn_error: &(dyn Error + Send + Sync) = any_error.as_ref();
+        assert_eq!(dyn_error.to_string(), any_error.to_string());
+        assert!(dyn_error.source().unwrap().is::<BlahError>());
+
+        // ... and so does a boxed error, e.g. one that was converted by `?`:
+        fn boxed(error: AnyError) -> Result<(), Box<dyn Error + Send + Sync>> {
+            Err(error)?
+        }
+        let message = any_error.to_string();
+        let boxed_error = boxed(any_error).unwrap_err();
+        assert_eq!(boxed_error.to_string(), message);
+        assert!(boxed_error.source().unwrap().is::<BlahError>());
+    }
+
+    #[test]
+    fn report() {
+        let blah_line = line!() + 1;
+        let blah_error = BlahError::new("blah", 1_usize);
+        let container_line = line!() + 1;
+        let container_error = ContainerError::new_BlahError(blah_error);
+        let outer_line = line!() + 2;
+        let container_container_error =
+            ContainerContainerError::new_ContainerError(container_error);
+        let error = &container_container_error;
+
+        let blah = "This is a blah error; - field0 = blah - field1 = 1";
+        let report = strip_colors(&Report::new(error));
+        let lines: Vec<_> = report.lines().collect();
+        if cfg!(feature = "example-build-flag") {
+            let file = file!();
+            assert!(lines[0].starts_with(&format!(
+                "Container: Blah: {blah} @ {file}:{outer_line}:"
+            )));
+            assert!(lines[1].starts_with(&format!(
+                "  caused by: Blah: {blah} @ {file}:{container_line}:"
+            )));
+            assert!(lines[2].starts_with(&format!(
+                "  caused by: {blah} @ {file}:{blah_line}:"
+            )));
+            assert_eq!(lines[3], "");
+            assert_eq!(report.matches("ERROR detected").count(), 1);
+            assert!(
+                lines[4].starts_with(&format!(
+                    "ERROR detected @ {file}:{blah_line}:"
+                ))
+            );
+
+            let report =
+                Report::new(error).backtrace(ReportBacktrace::Layer(0));
+            let report = strip_colors(&report);
+            assert_eq!(report.matches("ERROR detected").count(), 1);
+            assert!(report.contains(&format!(
+                "\n\nERROR detected @ {file}:{outer_line}:"
+            )));
+
+            // Rendering the error itself is unaffected by reports:
+            let full = error.render(RenderStyle::Full).to_string();
+            assert_eq!(full.matches("ERROR detected").count(), 3);
+        } else {
+            assert_eq!(
+                lines,
+                [
+                    &*format!("Container: Blah: {blah}"),
+                    &*format!("  caused by: Blah: {blah}"),
+                    &*format!("  caused by: {blah}"),
+                ]
+            );
+        }
+        let report = Report::new(error).backtrace(ReportBacktrace::Hidden);
+        let report = strip_colors(&report);
+        assert!(!report.contains("ERROR detected"));
+        assert_eq!(report.lines().count(), 3);
+    }
+
+    #[test]
+    fn report_with_unannotated_layer() {
+        let blah_line = line!() + 1;
+        let blah_error = BlahError::new("blah", 1_usize);
+        let outer_line = line!() + 1;
+        let error = ForeignContainerError::new(ForeignWrapperError(blah_error));
+
+        let blah = "This is a blah error; - field0 = blah - field1 = 1";
+        let report = strip_colors(&Report::new(&error));
+        let lines: Vec<_> = report.lines().collect();
+        // The middle layer has no location of its own, even though the
+        // annotated error in its message has one:
+        assert_eq!(lines[1], format!("  caused by: foreign wrapper: {blah}"));
+        if cfg!(feature = "example-build-flag") {
+            let file = file!();
+            assert!(lines[0].starts_with(&format!(
+                "Foreign: foreign wrapper: {blah} @ {file}:{outer_line}:"
+            )));
+            assert!(lines[2].starts_with(&format!(
+                "  caused by: {blah} @ {file}:{blah_line}:"
+            )));
+            assert_eq!(report.matches("ERROR detected").count(), 1);
+
+            // A layer without ctx has no ctx to render:
+            let report =
+                Report::new(&error).backtrace(ReportBacktrace::Layer(1));
+            let report = strip_colors(&report);
+            assert!(!report.contains("ERROR detected"));
+            assert_eq!(report.lines().count(), 3);
+        } else {
+            assert_eq!(
+                lines,
+                [
+                    &*format!("Foreign: foreign wrapper: {blah}"),
+                    &*format!("  caused by: foreign wrapper: {blah}"),
+                    &*format!("  caused by: {blah}"),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn input_spans() {
+        let input =
+            Input::new("config.toml", "name = \"x\"\nport = \"eighty\"\n");
+        let port = input.source().find("\"eighty\"").unwrap();
+        let result = Err::<(), _>(BlahError::new("blah", 1_usize))
+            .with_span(&input, port..port + 8, "expected an integer")
+            .with_span(&input, 0..input.source().len(), "");
+        let error = result.unwrap_err();
+        let Some(ctx) = error.ctx() else { return };
+        assert_eq!(ctx.spans().len(), 2);
+        assert_eq!(ctx.spans()[0].input().name(), "config.toml");
+        assert_eq!(ctx.spans()[0].range(), port..port + 8);
+        assert_eq!(ctx.spans()[0].label(), "expected an integer");
+
+        let rendered = strip_colors(ctx);
+        assert!(rendered.contains(concat!(
+            " --> config.toml:2:8\n",
+            "  |\n",
+            "2 | port = \"eighty\"\n",
+            "  |        ^^^^^^^^ expected an integer\n",
+        )));
+        assert!(rendered.contains(concat!(
+            " --> config.toml:1:1\n",
+            "  |\n",
+            "1 | name = \"x\"\n",
+            "  | ^^^^^^^^^^\n",
+            "2 | port = \"eighty\"\n",
+            "  | ^^^^^^^^^^^^^^^\n",
+        )));
+
+        // Out of range spans are clamped:
+        let mut ctx = ErrorCtx::new();
+        ctx.add_span(&input, 100..200, "at the end");
+        let rendered = strip_colors(&ctx);
+        assert!(rendered.contains(" --> config.toml:3:1\n"));
+        assert!(rendered.contains("  | ^ at the end\n"));
+
+        // Spans are kept by a thin ctx:
+        let thin_ctx = ThinErrorCtx::from(ctx);
+        assert_eq!(thin_ctx.as_ctx().spans().len(), 1);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let input = Input::new("config.toml", "port = \"eighty\"\n");
+        let result = Err::<(), _>(BlahError::new("blah", 1_usize))
+            .with_span(&input, 7..15, "expected an integer")
+            .trace();
+        let error = result.unwrap_err();
+        let Some(ctx) = error.ctx() else { return };
+
+        let json = serde_json::to_value(ctx).unwrap();
+        assert_eq!(json["location"]["file"], ctx.file());
+        assert_eq!(json["location"]["line"], ctx.line());
+        assert_eq!(json["location"]["column"], ctx.column());
+        let status = match ctx.backtrace_status() {
+            BacktraceStatus::Captured => "captured",
+            BacktraceStatus::Disabled => "disabled",
+            _ => "unsupported",
+        };
+        assert_eq!(json["backtrace_status"], status);
+        let frames = json["frames"].as_array().unwrap();
+        assert_eq!(frames.len(), ctx.frames().len());
+        assert_eq!(json["trail"].as_array().unwrap().len(), 1);
+        assert_eq!(json["spans"][0]["label"], "expected an integer");
+        assert_eq!(json["spans"][0]["range"]["start"], 7);
+
+        // The deserialized ctx renders the same output:
+        let owned: OwnedErrorCtx =
+            serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(owned, OwnedErrorCtx::from(ctx));
+        assert_eq!(strip_colors(&owned), strip_colors(ctx));
+
+        // So does a restored `ErrorCtx`, which keeps the location and trail:
+        let restored: ErrorCtx = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.location(), ctx.location());
+        assert_eq!(restored.trail(), ctx.trail());
+        assert_eq!(restored.frames(), ctx.frames());
+        assert_eq!(strip_colors(&restored), strip_colors(ctx));
+
+        // The trail and the spans are optional:
+        let json = serde_json::json!({
+            "location": { "file": "src/lib.rs", "line": 3, "column": 5 },
+            "backtrace_status": "disabled",
+            "frames": [],
+        });
+        let owned: OwnedErrorCtx = serde_json::from_value(json).unwrap();
+        assert!(owned.trail.is_empty());
+        assert!(owned.spans.is_empty());
+        assert!(strip_colors(&owned).contains("src/lib.rs:3:5"));
+    }
+
+    #[test]
+    fn serde_annotated_types() {
+        let line = line!() + 1;
+        let error = SerdeCtxError::new(5_u64);
+        let json = serde_json::to_value(&error).unwrap();
+        let restored: SerdeCtxError =
+            serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(restored.0, 5);
+        assert_eq!(strip_colors(&restored), strip_colors(&error));
+        assert_eq!(serde_json::to_value(&restored).unwrap(), json);
+        let Some(ctx) = restored.ctx() else { return };
+        assert_eq!(json[1]["location"]["line"], line);
+        assert_eq!(
+            ctx.backtrace_status(),
+            error.ctx().unwrap().backtrace_status()
+        );
+        assert_eq!(ctx.frames(), error.ctx().unwrap().frames());
+
+        // A missing context is replaced by a placeholder:
+        let restored: SerdeCtxError =
+            serde_json::from_value(serde_json::json!([7])).unwrap();
+        assert_eq!(restored.0, 7);
+        assert_eq!(
+            restored.ctx().unwrap().backtrace_status(),
+            BacktraceStatus::Disabled
+        );
+        let json = serde_json::json!({ "Named": { "f0": 3 } });
+        let restored: SerdeThinCtxEnumError =
+            serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            restored,
+            SerdeThinCtxEnumError::Named { f0: 3, .. }
+        ));
+        assert_eq!(
+            restored.ctx().unwrap().backtrace_status(),
+            BacktraceStatus::Disabled
+        );
+
+        // A thin context is restored as well:
+        let error = SerdeThinCtxEnumError::new_Unit();
+        let json = serde_json::to_value(&error).unwrap();
+        assert!(json["Unit"]["ctx"]["location"].is_object());
+        let restored: SerdeThinCtxEnumError =
+            serde_json::from_value(json).unwrap();
+        assert_eq!(strip_colors(&restored), strip_colors(&error));
+
+        // A skipped context isn't serialized:
+        let error = SerdeSkipCtxError::new("blah");
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json, serde_json::json!({ "f0": "blah" }));
+        let restored: SerdeSkipCtxError = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.f0, "blah");
+        assert_eq!(
+            restored.ctx().unwrap().backtrace_status(),
+            BacktraceStatus::Disabled
+        );
+
+        // Neither is a zero-sized one (unless "disabled-build-flag" is
+        // enabled, in which case it's a regular context):
+        let error = DisabledSerdeCtxError::new(9_u64);
+        let json = serde_json::to_value(&error).unwrap();
+        if cfg!(feature = "disabled-build-flag") {
+            assert!(json[1]["location"].is_object());
+        } else {
+            assert_eq!(json, serde_json::json!([9]));
+        }
+        let restored: DisabledSerdeCtxError =
+            serde_json::from_value(json).unwrap();
+        assert_eq!(restored.0, 9);
+    }
+
+    #[test]
+    fn synthetic_locations() {
+        let mut ctx = ErrorCtx::at("src/fixture.rs", 12, 5);
+        assert_eq!(ctx.file(), "src/fixture.rs");
+        assert_eq!((ctx.line(), ctx.column()), (12, 5));
+        assert!(ctx.location().as_static().is_none());
+        assert_eq!(ctx.backtrace_status(), BacktraceStatus::Disabled);
+        assert!(ctx.frames().is_empty());
+        assert_eq!(
+            strip_colors(&ctx),
+            "ERROR detected @ src/fixture.rs:12:5:\ndisabled backtrace\n",
+        );
+        ctx.trace();
+        assert!(
+            strip_colors(&ctx)
+                .contains(&format!("  propagated @ {}", ctx.trail()[0]))
+        );
+
+        // A context can be created for a location that may not be 'static,
+        // which is copied:
+        fn location_ctx(location: &Location<'_>) -> ErrorCtx {
+            ErrorCtx::from_location(location)
+        }
+        let (ctx, line) = (location_ctx(Location::caller()), line!());
+        assert_eq!(ctx.file(), file!());
+        assert_eq!(ctx.line(), line);
+        assert!(ctx.location().as_static().is_none());
+
+        // Owned and captured locations compare equal when they're equal:
+        let (caller, line) = (Location::caller(), line!());
+        let captured = CtxLocation::from(caller);
+        assert!(captured.as_static().is_some());
+        assert_eq!(ErrorCtx::from_location(caller).location(), &captured);
+        let owned = CtxLocation::from(SourceLocation {
+            file: caller.file().to_string(),
+            line: caller.line(),
+            column: caller.column(),
+        });
+        assert_eq!(owned, captured);
+        assert_eq!(
+            owned.to_string(),
+            format!("{}:{line}:{}", file!(), caller.column())
+        );
+
+        // Thin contexts and reports treat owned locations the same:
+        let thin_ctx = ThinErrorCtx::from(ErrorCtx::at("src/fixture.rs", 3, 1));
+        assert_eq!(thin_ctx.location().to_string(), "src/fixture.rs:3:1");
+        assert_eq!(thin_ctx.into_ctx().line(), 3);
+        let mut error = BlahError::new("blah", 1_usize);
+        let Some(ctx) = error.ctx_mut() else { return };
+        *ctx = ErrorCtx::at("src/fixture.rs", 7, 9);
+        let report = strip_colors(&Report::new(&error));
+        assert!(report.contains(
+            " @ src/fixture.rs:7:9\n\nERROR detected @ src/fixture.rs:7:9:\n"
+        ));
+    }
+
+    #[test]
+    fn normalized_rendering() {
+        // The backtrace is replaced with a marker, whether or not it was
+        // captured:
+        let new_ctx = |policy| ErrorCtx::with_capture_policy(policy);
+        let captured = new_ctx(CapturePolicy::Force);
+        let not_captured = new_ctx(CapturePolicy::LocationOnly);
+        let normalized = Normalized::new(&captured).to_string();
+        assert_eq!(normalized, Normalized::new(&not_captured).to_string());
+        assert_eq!(
+            normalized,
+            format!(
+                "ERROR detected @ {}:{}:{}:\n<backtrace>\n",
+                file!(),
+                captured.line(),
+                captured.column(),
+            ),
+        );
+
+        // Absolute paths in the workspace are made relative to it:
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let file = workspace
+            .join("err-marks-the-spot-test")
+            .join("src")
+            .join("lib.rs");
+        let mut ctx = ErrorCtx::at(file.to_str().unwrap(), 3, 5);
+        ctx.trace();
+        let trail_location = &ctx.trail()[0];
+        assert_eq!(
+            Normalized::new(&ctx).to_string(),
+            format!(
+                "ERROR detected @ err-marks-the-spot-test/src/lib.rs:3:5:\n  \
+                 propagated @ {trail_location}\n<backtrace>\n",
+            ),
+        );
+        // ... but only while they're normalized:
+        assert!(ctx.to_string().contains(file.to_str().unwrap()));
+
+        // Annotated errors and reports are normalized as a whole:
+        let error =
+            ContainerError::new_BlahError(BlahError::new("blah", 1_usize));
+        let normalized = Normalized::new(&Report::new(&error)).to_string();
+        assert!(!normalized.contains("\x1b"));
+        if error.ctx().is_some() {
+            assert!(normalized.ends_with("\n<backtrace>\n"), "{normalized}");
+        }
+        let normalized =
+            Normalized::new(&error.render(RenderStyle::Full)).to_string();
+        assert_eq!(
+            normalized.matches("<backtrace>").count(),
+            2 * error.ctx().iter().count()
+        );
+    }
 }
@@ -0,0 +1,51 @@
+//! The process-wide capture policy is tested in its own process, so that it
+//! doesn't affect backtrace capturing in other tests.
+
+use err_marks_the_spot::{
+    CAPTURE_POLICY_ENV_VAR, CapturePolicy, ErrorMessage, HasErrorCtx,
+    RenderStyle, Report, capture_policy, set_capture_policy,
+};
+use err_marks_the_spot_test::{AlwaysBacktraceError, BlahError};
+use std::backtrace::BacktraceStatus;
+
+fn backtrace_status(error: &impl HasErrorCtx) -> Option<BacktraceStatus> {
+    error.ctx().map(|ctx| ctx.backtrace_status())
+}
+
+#[test]
+fn process_wide_capture_policy() {
+    // SAFETY: This is the only test in this process, and it sets the
+    //         environment variables before any thread reads them.
+    unsafe {
+        std::env::set_var(CAPTURE_POLICY_ENV_VAR, "location");
+        std::env::set_var("RUST_LIB_BACKTRACE", "1");
+    }
+    if !cfg!(feature = "example-build-flag") {
+        return;
+    }
+    assert_eq!(capture_policy(), CapturePolicy::LocationOnly);
+    let error = BlahError::new("blah", 1_usize);
+    assert_eq!(backtrace_status(&error), Some(BacktraceStatus::Disabled));
+    let rendered = error.render(RenderStyle::Full).to_string();
+    assert!(rendered.contains("ERROR detected @ "));
+
+    set_capture_policy(CapturePolicy::Env);
+    let error = BlahError::new("blah", 2_usize);
+    assert_eq!(backtrace_status(&error), Some(BacktraceStatus::Captured));
+
+    // The policy of a type takes precedence over the process-wide policy:
+    set_capture_policy(CapturePolicy::LocationOnly);
+    let error = AlwaysBacktraceError::new_Tuple(3_u64);
+    assert_eq!(backtrace_status(&error), Some(BacktraceStatus::Captured));
+
+    // ... except for `CapturePolicy::Off`, which only disables capturing:
+    set_capture_policy(CapturePolicy::Off);
+    let error = AlwaysBacktraceError::new_Tuple(4_u64);
+    assert_eq!(backtrace_status(&error), Some(BacktraceStatus::Disabled));
+    let rendered = error.render(RenderStyle::Full).to_string();
+    assert!(rendered.starts_with("Always captures a backtrace: 4\n\n"));
+    assert!(rendered.contains("ERROR detected @ "));
+    assert!(rendered.contains("disabled backtrace"));
+    let report = Report::new(&error).to_string();
+    assert!(report.starts_with("Always captures a backtrace: 4 @ "));
+}
@@ -0,0 +1,33 @@
+//! The process-wide color policy is tested in its own process, so that it
+//! doesn't affect the rendering in other tests.
+
+use err_marks_the_spot::{
+    ColorPolicy, ErrorMessage, Normalized, RenderStyle, color_policy,
+    set_color_policy,
+};
+use err_marks_the_spot_test::BlahError;
+
+#[test]
+fn process_wide_color_policy() {
+    assert_eq!(color_policy(), ColorPolicy::Auto);
+    let blah_error = BlahError::new("blah error msg", 57_usize);
+    let full = blah_error.render(RenderStyle::Full);
+    set_color_policy(ColorPolicy::Never);
+    let plain = full.to_string();
+    set_color_policy(ColorPolicy::Always);
+    let colored = full.to_string();
+    // Normalized output is never colored:
+    let normalized = Normalized::new(&full).to_string();
+    set_color_policy(ColorPolicy::Auto);
+
+    assert!(!plain.contains('\x1b'));
+    assert!(!normalized.contains('\x1b'));
+    if cfg!(feature = "example-build-flag") {
+        assert!(plain.contains(
+            "ERROR detected @ err-marks-the-spot-test/tests/color_policy.rs:"
+        ));
+        assert!(colored.contains("\x1b["));
+    } else {
+        assert_eq!(plain, colored);
+    }
+}
@@ -0,0 +1,58 @@
+//! Backtrace frame filtering is tested in its own process, so that a
+//! backtrace can be captured regardless of the environment.
+
+use err_marks_the_spot::{
+    ColorPolicy, FrameFilter, HasErrorCtx, frame_filter, set_color_policy,
+    set_frame_filter,
+};
+use err_marks_the_spot_test::BlahError;
+
+#[test]
+fn frame_filter_hides_and_highlights_frames() {
+    // SAFETY: This is the only test in this process, and it sets the
+    //         environment variable before any thread reads it.
+    unsafe { std::env::set_var("RUST_LIB_BACKTRACE", "1") };
+    set_color_policy(ColorPolicy::Never);
+
+    let error = BlahError::new("blah", 1_usize);
+    let Some(ctx) = error.ctx() else { return };
+    let frames = ctx.frames();
+    let origin = frames
+        .iter()
+        .find(|frame| {
+            let function = frame.function();
+            function.ends_with("frame_filter_hides_and_highlights_frames")
+        })
+        .unwrap();
+    assert_eq!(origin.crate_name(), Some("frame_filter"));
+    assert!(!origin.is_runtime());
+    assert!(frames.iter().any(|frame| frame.is_runtime()));
+
+    assert_eq!(frame_filter(), FrameFilter::new());
+    let filtered = ctx.to_string();
+    assert!(filtered.contains("frames hidden"));
+    assert!(filtered.contains("frame_filter_hides_and_highlights_frames"));
+    assert!(!filtered.contains("std::"));
+    assert!(!filtered.contains("err_marks_the_spot_core::ErrorCtx::new"));
+
+    set_frame_filter(FrameFilter::show_all());
+    let unfiltered = ctx.to_string();
+    assert!(!unfiltered.contains("hidden"));
+    assert!(unfiltered.contains("err_marks_the_spot_core::ErrorCtx::new"));
+
+    set_frame_filter(FrameFilter {
+        max_frames: Some(1),
+        ..FrameFilter::new()
+    });
+    let capped = ctx.to_string();
+    assert!(capped.contains("more frames omitted"));
+    assert!(capped.contains("err_marks_the_spot_test::BlahError::new"));
+    assert!(!capped.contains("frame_filter_hides_and_highlights_frames"));
+
+    // Frames of the crate that created the error are highlighted:
+    set_color_policy(ColorPolicy::Always);
+    set_frame_filter(FrameFilter::new());
+    let highlighted = ctx.to_string();
+    assert!(highlighted.contains("\x1b[36mframe_filter::"));
+    assert!(!highlighted.contains("\x1b[36merr_marks_the_spot_test::"));
+}
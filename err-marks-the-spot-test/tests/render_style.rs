@@ -0,0 +1,60 @@
+//! The process-wide render style is tested in its own process, so that it
+//! doesn't affect the rendering in other tests.
+
+use err_marks_the_spot::{
+    ColorPolicy, RenderStyle, render_style, set_color_policy, set_render_style,
+};
+use err_marks_the_spot_test::{
+    BlahError, ContainerError, MessageRenderedError,
+};
+
+#[test]
+fn process_wide_render_style() {
+    set_color_policy(ColorPolicy::Never);
+    assert_eq!(render_style(), RenderStyle::Message);
+    let blah_error = BlahError::new("blah", 1_usize);
+    let message = "This is a blah error;\n  - field0 = blah\n  - field1 = 1";
+    assert_eq!(blah_error.to_string(), message);
+
+    set_render_style(RenderStyle::Full);
+    assert_eq!(render_style(), RenderStyle::Full);
+    let full = blah_error.to_string();
+    assert!(full.starts_with(message));
+    if cfg!(feature = "example-build-flag") {
+        assert!(full.contains("\n\nERROR detected @ "));
+    }
+
+    set_render_style(RenderStyle::SingleLine);
+    let single_line = blah_error.to_string();
+    assert!(
+        single_line
+            .starts_with("This is a blah error; - field0 = blah - field1 = 1")
+    );
+    assert!(!single_line.contains('\n'));
+
+    // A nested error is rendered as a message, so there's a single location:
+    let container_error =
+        ContainerError::new_BlahError(BlahError::new("blah", 1_usize));
+    let single_line = container_error.to_string();
+    assert!(single_line.starts_with(
+        "Blah: This is a blah error; - field0 = blah - field1 = 1"
+    ));
+    let locations = single_line.matches(" @ ").count();
+    if cfg!(feature = "example-build-flag") {
+        assert_eq!(locations, 1);
+    } else {
+        assert_eq!(locations, 0);
+    }
+
+    // The render attribute argument takes precedence:
+    let message_error = MessageRenderedError::new(42_usize);
+    assert!(message_error.to_string().contains('\n'));
+
+    set_render_style(RenderStyle::Message);
+    let container_error =
+        ContainerError::new_BlahError(BlahError::new("blah", 1_usize));
+    assert_eq!(
+        container_error.to_string(),
+        "Blah: This is a blah error;\n  - field0 = blah\n  - field1 = 1",
+    );
+}
@@ -0,0 +1,53 @@
+//! Source snippets are tested in their own process, because they're enabled
+//! process-wide.
+
+use err_marks_the_spot::{
+    ColorPolicy, ErrorCtx, SourceSnippets, set_color_policy,
+    set_source_snippets, source_snippets,
+};
+
+#[test]
+fn source_snippets_show_the_code_around_locations() {
+    // SAFETY: This is the only test in this process, and it sets the
+    //         environment variable before any thread reads it.
+    unsafe { std::env::set_var("RUST_LIB_BACKTRACE", "1") };
+    set_color_policy(ColorPolicy::Never);
+
+    let ctx = ErrorCtx::new();
+    let line = ctx.line();
+    assert_eq!(source_snippets(), None);
+    assert!(!ctx.to_string().contains(" | "));
+
+    set_source_snippets(Some(SourceSnippets {
+        context_lines: 1,
+        frames: 0,
+    }));
+    let rendered = ctx.to_string();
+    let caret = format!("{}^", " ".repeat(ctx.column() as usize - 1));
+    let w = (line + 1).to_string().len();
+    let expected = [
+        format!("{:w$} |", ""),
+        format!("{:>w$} ", line - 1),
+        format!("{line:>w$} |     let ctx = ErrorCtx::new();"),
+        format!("{:w$} | {caret}", ""),
+        format!("{:>w$} |     let line = ctx.line();", line + 1),
+    ];
+    let lines: Vec<&str> = rendered.lines().skip(1).take(5).collect();
+    assert_eq!(lines[0], expected[0]);
+    assert!(lines[1].starts_with(&expected[1]));
+    assert_eq!(lines[2..], expected[2..]);
+    assert_eq!(rendered.matches('^').count(), 1);
+
+    // Backtrace frames outside of the runtime get a snippet as well:
+    set_source_snippets(Some(SourceSnippets {
+        context_lines: 0,
+        frames: 1,
+    }));
+    let rendered = ctx.to_string();
+    if !ctx.frames().is_empty() {
+        assert_eq!(rendered.matches('^').count(), 2);
+        let frame_snippet =
+            format!("             {line} |     let ctx = ErrorCtx::new();");
+        assert!(rendered.contains(&frame_snippet));
+    }
+}
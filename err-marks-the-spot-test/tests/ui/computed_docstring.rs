@@ -0,0 +1,19 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+#[doc = concat!("This error has a field ", "{f0}")]
+#[err_marks_the_spot]
+#[derive(Debug)]
+pub struct MyStructError {
+    f0: String,
+}
+
+#[err_marks_the_spot]
+#[derive(Debug)]
+pub enum MyEnumError {
+    /// This variant is documented with a literal
+    Literal,
+    #[doc = include_str!("computed_docstring.txt")]
+    Included,
+}
+
+fn main() {}
@@ -0,0 +1,21 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+#[err_marks_the_spot]
+#[derive(Debug)]
+pub struct MyStructError {
+    ctx: String,
+}
+
+#[err_marks_the_spot(ctx_field = "origin", ctx_vis = "pub(crate)")]
+#[derive(Debug)]
+pub enum MyEnumError {
+    Named { origin: String },
+}
+
+#[err_marks_the_spot(ctx_field = "not an ident", ctx_vis = "public")]
+#[derive(Debug)]
+pub struct MyOtherStructError {
+    f0: String,
+}
+
+fn main() {}
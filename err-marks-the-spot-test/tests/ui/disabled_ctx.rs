@@ -0,0 +1,15 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+#[err_marks_the_spot(disabled_ctx = "zero_sized")]
+#[derive(Debug)]
+pub struct MyError {
+    f0: String,
+}
+
+#[err_marks_the_spot(feature = "example-build-flag", disabled_ctx = "gone")]
+#[derive(Debug)]
+pub struct MyOtherError {
+    f0: String,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+#[err_marks_the_spot]
+#[derive(Debug)]
+pub enum MyError {
+    /// First
+    First = 1,
+    /// Second
+    Second = 2,
+}
+
+fn main() {}
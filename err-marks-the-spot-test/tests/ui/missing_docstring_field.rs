@@ -0,0 +1,19 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+/// This error has a field {f0} but not {f1}
+#[err_marks_the_spot]
+#[derive(Debug)]
+pub struct MyStructError {
+    f0: String,
+}
+
+#[err_marks_the_spot]
+#[derive(Debug)]
+pub enum MyEnumError {
+    /// This variant has a field {0} but not {2}
+    Tuple(usize),
+    /// This variant has a field {f0} but not {f1}
+    Named { f0: String },
+}
+
+fn main() {}
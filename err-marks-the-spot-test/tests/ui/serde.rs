@@ -0,0 +1,9 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+#[err_marks_the_spot(serde = "flatten")]
+#[derive(Debug)]
+pub struct MyError {
+    f0: String,
+}
+
+fn main() {}
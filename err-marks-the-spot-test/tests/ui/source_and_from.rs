@@ -0,0 +1,17 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+#[err_marks_the_spot]
+#[derive(Debug)]
+pub struct MyStructError {
+    #[from]
+    inner: std::io::Error,
+}
+
+#[err_marks_the_spot]
+#[derive(Debug)]
+pub enum MyEnumError {
+    TwoSources(#[source] std::io::Error, #[source] std::fmt::Error),
+    TwoFields(#[from] std::fmt::Error, usize),
+}
+
+fn main() {}
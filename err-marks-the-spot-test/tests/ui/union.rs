@@ -0,0 +1,9 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+#[err_marks_the_spot]
+pub union MyError {
+    f0: u32,
+    f1: f32,
+}
+
+fn main() {}
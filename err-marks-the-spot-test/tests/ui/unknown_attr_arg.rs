@@ -0,0 +1,15 @@
+use err_marks_the_spot::err_marks_the_spot;
+
+#[err_marks_the_spot(featur = "x", inline_ctors(sometimes))]
+#[derive(Debug)]
+pub struct MyError {
+    f0: String,
+}
+
+#[err_marks_the_spot(crate = "not a path")]
+#[derive(Debug)]
+pub struct MyOtherError {
+    f0: String,
+}
+
+fn main() {}
@@ -0,0 +1,386 @@
+//! A type-erased error type for application code.
+
+use err_marks_the_spot_core::{
+    CtxLocation, ErrorCtx, ErrorMessage, HasErrorCtx, Located, RenderStyle,
+};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::panic::Location;
+
+/// A type-erased error that always carries an [`ErrorCtx`], plus a stack of
+/// human-readable context messages, each with the location where it was added.
+///
+/// Annotated error types (and [`Located`] errors) convert into an `AnyError`
+/// with `?`, which reuses their `ErrorCtx` rather than capturing a second one.
+/// Errors of any other type are wrapped with [`AnyError::new`] instead, or
+/// with `.located()?` (see [`ResultExt::located`]).
+///
+/// NOTE: There is no `From` impl for errors of any type, because it would
+///       overlap with the one for annotated types. For the same reason,
+///       [`ContextExt`] only accepts annotated and `Located` errors, so
+///       e.g. an `io::Result` needs `.located().context(..)`.
+///
+/// [`ResultExt::located`]: err_marks_the_spot_core::ResultExt::located
+///
+/// The `Display` impl renders the context messages from the outermost one to
+/// the innermost one, followed by the message of the error, e.g.
+/// `failed to start: failed to load config: invalid digit found in string`.
+/// In [`RenderStyle::Full`], the `ErrorCtx` is followed by the location of
+/// each context message.
+///
+/// `AnyError` doesn't implement `Error` itself, because then the `From` impl
+/// for annotated types would overlap with `impl From<T> for T`. Use
+/// [`AnyError::as_dyn_error`] (or `AsRef`) to borrow it as an `Error`, e.g.
+/// for a [`Report`](err_marks_the_spot_core::Report), or convert it into a
+/// `Box<dyn Error + Send + Sync>`.
+pub struct AnyError {
+    inner: Box<Inner>,
+}
+
+struct Inner {
+    error: Box<dyn ErrorObject>,
+    /// The context of the error if it has none itself, e.g. because the
+    /// feature of its type is disabled.
+    ctx: Option<ErrorCtx>,
+    /// The context messages, from the innermost one to the outermost one.
+    context: Vec<Context>,
+}
+
+#[derive(Debug)]
+struct Context {
+    message: String,
+    location: CtxLocation,
+}
+
+impl AnyError {
+    /// Wrap an `error` of any type, recording the caller's location.
+    ///
+    /// Prefer `AnyError::from()` (or `?`) for annotated error types,
+    /// which reuses their `ErrorCtx`.
+    #[track_caller]
+    pub fn new<E>(error: E) -> Self
+    where
+        E: Error + Send + Sync + 'static,
+    {
+        Self::from_object(Box::new(Located::new(error)), None)
+    }
+
+    fn from_object(error: Box<dyn ErrorObject>, ctx: Option<ErrorCtx>) -> Self {
+        let context = vec![];
+        Self {
+            inner: Box::new(Inner {
+                error,
+                ctx,
+                context,
+            }),
+        }
+    }
+
+    /// Add a context `message`, recording the caller's location.
+    #[track_caller]
+    pub fn context<C: Display>(mut self, message: C) -> Self {
+        self.inner.context.push(Context {
+            message: message.to_string(),
+            location: CtxLocation::from(Location::caller()),
+        });
+        self
+    }
+
+    /// The context messages and their locations, from the innermost one to
+    /// the outermost one.
+    pub fn contexts(&self) -> impl Iterator<Item = (&str, &CtxLocation)> {
+        let context = self.inner.context.iter();
+        context.map(|context| (&*context.message, &context.location))
+    }
+
+    /// The wrapped error, as a trait object.
+    pub fn as_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+        self.inner.error.as_error()
+    }
+
+    /// This `AnyError` as a trait object, e.g. for a
+    /// [`Report`](err_marks_the_spot_core::Report). Unlike
+    /// [`AnyError::as_error`], it renders the context messages and the
+    /// `ErrorCtx` as well, and its `source()` is the source of the wrapped
+    /// error.
+    pub fn as_dyn_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.inner
+    }
+
+    /// Whether the wrapped error is a `T`, or a `Located<T>`.
+    pub fn is<T: Error + 'static>(&self) -> bool {
+        let error = self.as_error();
+        error.is::<T>() || error.is::<Located<T>>()
+    }
+
+    /// A reference to the wrapped error, if it is a `T` (or a `Located<T>`).
+    pub fn downcast_ref<T: Error + 'static>(&self) -> Option<&T> {
+        let error = self.as_error();
+        match error.downcast_ref::<Located<T>>() {
+            Some(located) => Some(located.error()),
+            None => error.downcast_ref::<T>(),
+        }
+    }
+
+    /// A mutable reference to the wrapped error, if it is a `T`
+    /// (or a `Located<T>`).
+    pub fn downcast_mut<T: Error + 'static>(&mut self) -> Option<&mut T> {
+        let error = self.inner.error.as_error_mut();
+        if error.is::<Located<T>>() {
+            let located = error.downcast_mut::<Located<T>>();
+            return located.map(Located::error_mut);
+        }
+        error.downcast_mut::<T>()
+    }
+
+    /// Unwrap the error if it is a `T` (or a `Located<T>`), discarding its
+    /// context. Otherwise, return `self` unchanged.
+    pub fn downcast<T: Error + 'static>(self) -> Result<T, Self> {
+        if !self.is::<T>() {
+            return Err(self);
+        }
+        let error = self.inner.error.into_error();
+        let error = match error.downcast::<Located<T>>() {
+            Ok(located) => return Ok(located.into_error()),
+            Err(error) => error,
+        };
+        match error.downcast::<T>() {
+            Ok(error) => Ok(*error),
+            Err(_) => unreachable!("the error was checked to be a `T`"),
+        }
+    }
+}
+
+/// Converting an annotated error reuses its `ErrorCtx`. If it has none, e.g.
+/// because its feature is disabled, the location of the conversion is
+/// recorded instead.
+impl<E> From<E> for AnyError
+where
+    E: ErrorMessage + Error + Send + Sync + 'static,
+{
+    #[track_caller]
+    fn from(error: E) -> Self {
+        let ctx = match HasErrorCtx::ctx(&error) {
+            Some(_) => None,
+            None => Some(ErrorCtx::new()),
+        };
+        Self::from_object(Box::new(error), ctx)
+    }
+}
+
+impl AsRef<dyn Error + Send + Sync + 'static> for AnyError {
+    fn as_ref(&self) -> &(dyn Error + Send + Sync + 'static) {
+        self.as_dyn_error()
+    }
+}
+
+impl From<AnyError> for Box<dyn Error + Send + Sync + 'static> {
+    fn from(error: AnyError) -> Self {
+        error.inner
+    }
+}
+
+impl HasErrorCtx for AnyError {
+    fn ctx(&self) -> Option<&ErrorCtx> {
+        HasErrorCtx::ctx(&*self.inner)
+    }
+
+    fn ctx_mut(&mut self) -> Option<&mut ErrorCtx> {
+        HasErrorCtx::ctx_mut(&mut *self.inner)
+    }
+
+    fn take_ctx(self) -> Option<ErrorCtx> {
+        HasErrorCtx::take_ctx(*self.inner)
+    }
+}
+
+impl ErrorMessage for AnyError {
+    fn fmt_message(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        ErrorMessage::fmt_message(&*self.inner, f)
+    }
+
+    fn fmt_details(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        ErrorMessage::fmt_details(&*self.inner, f)
+    }
+}
+
+impl Display for AnyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&*self.inner, f)
+    }
+}
+
+impl fmt::Debug for AnyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.inner, f)
+    }
+}
+
+impl HasErrorCtx for Inner {
+    fn ctx(&self) -> Option<&ErrorCtx> {
+        let Inner { error, ctx, .. } = self;
+        ctx.as_ref().or_else(|| error.ctx())
+    }
+
+    fn ctx_mut(&mut self) -> Option<&mut ErrorCtx> {
+        let Inner { error, ctx, .. } = self;
+        match ctx {
+            Some(ctx) => Some(ctx),
+            None => error.ctx_mut(),
+        }
+    }
+
+    fn take_ctx(self) -> Option<ErrorCtx> {
+        let Inner { error, ctx, .. } = self;
+        ctx.or_else(|| error.take_ctx_boxed())
+    }
+}
+
+impl ErrorMessage for Inner {
+    fn fmt_message(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for context in self.context.iter().rev() {
+            write!(f, "{}: ", context.message)?;
+        }
+        self.error.fmt_message(f)
+    }
+
+    fn fmt_details(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.context.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "Context:")?;
+        for (idx, context) in self.context.iter().enumerate() {
+            writeln!(f, "{idx:>4}: {}", context.message)?;
+            writeln!(f, "             at {}", context.location)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Inner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        RenderStyle::select(f, None).fmt_error(self, f)
+    }
+}
+
+impl fmt::Debug for Inner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnyError")
+            .field("error", &self.error)
+            .field("ctx", &self.ctx)
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
+/// The message of the wrapped error is part of the message of the
+/// `AnyError`, so its source is the source of the wrapped error.
+impl Error for Inner {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.as_error().source()
+    }
+}
+
+/// An annotated (or `Located`) error, which can be stored in an `AnyError`.
+///
+/// NOTE: `ErrorMessage` and `HasErrorCtx` cannot be supertraits of this trait,
+///       because `ErrorMessage` isn't dyn compatible.
+trait ErrorObject: Error + Send + Sync + 'static {
+    fn ctx(&self) -> Option<&ErrorCtx>;
+
+    fn ctx_mut(&mut self) -> Option<&mut ErrorCtx>;
+
+    fn fmt_message(&self, f: &mut Formatter<'_>) -> fmt::Result;
+
+    fn as_error(&self) -> &(dyn Error + Send + Sync + 'static);
+
+    fn as_error_mut(&mut self) -> &mut (dyn Error + Send + Sync + 'static);
+
+    fn into_error(self: Box<Self>) -> Box<dyn Error + Send + Sync + 'static>;
+
+    fn take_ctx_boxed(self: Box<Self>) -> Option<ErrorCtx>;
+}
+
+impl<E> ErrorObject for E
+where
+    E: ErrorMessage + Error + Send + Sync + 'static,
+{
+    fn ctx(&self) -> Option<&ErrorCtx> {
+        HasErrorCtx::ctx(self)
+    }
+
+    fn ctx_mut(&mut self) -> Option<&mut ErrorCtx> {
+        HasErrorCtx::ctx_mut(self)
+    }
+
+    fn fmt_message(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        ErrorMessage::fmt_message(self, f)
+    }
+
+    fn as_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn take_ctx_boxed(self: Box<Self>) -> Option<ErrorCtx> {
+        HasErrorCtx::take_ctx(*self)
+    }
+}
+
+/// Extension methods for `Result`s whose error converts into an [`AnyError`],
+/// i.e. annotated and [`Located`] errors. Wrap errors of other types with
+/// `.located()` first:
+/// ```ignore
+/// let text = std::fs::read_to_string(path).located().context("failed")?;
+/// ```
+pub trait ContextExt<T> {
+    /// Convert the error (if any) into an `AnyError`, and add a context
+    /// `message` to it, recording the caller's location:
+    /// ```ignore
+    /// let config = load_config(path).context("failed to load config")?;
+    /// ```
+    #[track_caller]
+    fn context<C: Display>(self, message: C) -> Result<T, AnyError>;
+
+    /// Like [`ContextExt::context`], but the message is only created if
+    /// there is an error.
+    #[track_caller]
+    fn with_context<C, F>(self, f: F) -> Result<T, AnyError>
+    where
+        C: Display,
+        F: FnOnce() -> C;
+}
+
+impl<T, E> ContextExt<T> for Result<T, E>
+where
+    AnyError: From<E>,
+{
+    #[track_caller]
+    fn context<C: Display>(self, message: C) -> Result<T, AnyError> {
+        // NOTE: `map_err()` would record a location inside of `map_err()`,
+        //       because closures cannot be `#[track_caller]`.
+        match self {
+            Ok(value) => Ok(value),
+            Err(error) => Err(AnyError::from(error).context(message)),
+        }
+    }
+
+    #[track_caller]
+    fn with_context<C, F>(self, f: F) -> Result<T, AnyError>
+    where
+        C: Display,
+        F: FnOnce() -> C,
+    {
+        match self {
+            Ok(value) => Ok(value),
+            Err(error) => Err(AnyError::from(error).context(f())),
+        }
+    }
+}
@@ -1,4 +1,16 @@
-//!
+//! A façade crate that exposes the functionality provided by this workspace.
 
-pub use err_marks_the_spot_core::ErrorCtx;
+mod any;
+
+pub use crate::any::{AnyError, ContextExt};
+pub use err_marks_the_spot_core::{
+    BACKTRACE_CACHE_CAPACITY, BacktraceFrame, CAPTURE_POLICY_ENV_VAR,
+    CapturePolicy, CaptureStatus, ColorPolicy, CtxLocation, ErrorCtx,
+    ErrorMessage, FrameFilter, HasErrorCtx, Input, InputSpan, Located,
+    NoErrorCtx, Normalized, OptionExt, OwnedErrorCtx, RenderStyle, Rendered,
+    Report, ReportBacktrace, ResultExt, SourceLocation, SourceSnippets,
+    ThinErrorCtx, capture_policy, color_policy, colors_enabled, frame_filter,
+    render_style, set_capture_policy, set_color_policy, set_frame_filter,
+    set_render_style, set_source_snippets, source_snippets,
+};
 pub use err_marks_the_spot_macro::err_marks_the_spot;